@@ -0,0 +1,120 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ogma_libs::clause::{self, Token};
+use ogma_libs::matcher::{MatchError, Matcher};
+use ogma_libs::module::{FuncDef, Index, Module, ModuleList};
+use ogma_libs::vm::{Callable, Context, Func, Trap};
+
+const SIZES: &[usize] = &[10, 100, 1000, 4000];
+
+/// A line which no step matches
+const MISS: &str = "Given the missing step of the input and 4 henceforth the output";
+
+/// The clause matched by every step but the last, which fails on its third word like the
+/// generated steps would
+const OTHER: &[Token<'static>] = &[
+    Token::Static("Given"),
+    Token::Static("the"),
+    Token::Static("other"),
+    Token::Static("step"),
+    Token::Static("of"),
+    Token::QueryVar("input"),
+    Token::Static("and"),
+    Token::DataVar("b", None),
+    Token::Static("henceforth"),
+    Token::QueryVar("out"),
+];
+
+/// The rest of the clause of the last step after its name
+const REST: &[Token<'static>] = &[
+    Token::Static("of"),
+    Token::QueryVar("input"),
+    Token::Static("and"),
+    Token::DataVar("b", None),
+    Token::Static("henceforth"),
+    Token::QueryVar("out"),
+];
+
+struct Noop;
+
+impl Callable for Noop {
+    fn call(&self, _: &mut Context) -> Result<(), Trap> {
+        Ok(())
+    }
+}
+
+fn match_clause<'a>(clause: &[Token], matcher: &mut Matcher<'a>) -> Result<(), MatchError> {
+    matcher.next_clause(clause, &mut |index, m| match clause[index] {
+        Token::QueryVar(_) => m.next_query().map(|_| ()),
+        Token::DataVar(..) => m.next_data::<i64>().map(|_| ()),
+        ref token => m.next_token(token),
+    })
+}
+
+fn match_other<'a>(_: &mut (), mut matcher: Matcher<'a>) -> Result<Func<'a>, MatchError> {
+    match_clause(OTHER, &mut matcher)?;
+    Ok(Box::new(Noop))
+}
+
+fn match_last<'a>(_: &mut (), mut matcher: Matcher<'a>) -> Result<Func<'a>, MatchError> {
+    matcher.next_phrase("Given the")?;
+    let start = matcher.clone();
+    if !matcher.next_static()?.starts_with("step") {
+        return Err(MatchError::MismatchedStaticToken(start.mismatch("step")));
+    }
+    match_clause(REST, &mut matcher)?;
+    Ok(Box::new(Noop))
+}
+
+/// The clause of a step, which only the index uses to tell the steps apart
+fn clause(step: usize) -> &'static [Token<'static>] {
+    let string = format!(
+        "Given the step{} of q`input` and d`b` henceforth q`out`",
+        step
+    );
+    let tokens = clause::parse(Box::leak(string.into_boxed_str()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    Box::leak(tokens.into_boxed_slice())
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    for &size in SIZES {
+        let definitions = (0..size)
+            .map(|step| FuncDef {
+                match_func: if step == size - 1 {
+                    match_last
+                } else {
+                    match_other
+                },
+                clause: clause(step),
+                priority: 0,
+            })
+            .collect::<Vec<_>>();
+        let line: &'static str = Box::leak(
+            format!(
+                "Given the step{} of the input and 4 henceforth the output",
+                size - 1
+            )
+            .into_boxed_str(),
+        );
+        let list: ModuleList<()> = definitions.clone().into_boxed_slice();
+        group.bench_with_input(BenchmarkId::new("linear", size), &line, |b, line| {
+            b.iter(|| list.compile_line(&mut (), line).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("linear-miss", size), &MISS, |b, line| {
+            b.iter(|| list.compile_line(&mut (), line).err().unwrap())
+        });
+        let index = Index::new(definitions);
+        group.bench_with_input(BenchmarkId::new("index", size), &line, |b, line| {
+            b.iter(|| index.compile_line(&mut (), line).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("index-miss", size), &MISS, |b, line| {
+            b.iter(|| index.compile_line(&mut (), line).err().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -1,9 +1,12 @@
 //! BDD utilities
 
+use crate::matcher::MatchContext;
+
 /// A BDD step
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default)]
 pub enum Step {
     /// Initial state of the BDD state
+    #[default]
     Start,
     /// Given: Parsing Input
     Given,
@@ -13,13 +16,24 @@ pub enum Step {
     Then,
 }
 
+/// A match context which tracks the BDD step of a script. BDD functions can be matched with any
+/// such context, such as one which pairs the step with a clock
+pub trait StepContext {
+    /// Get the current BDD step
+    fn step(&self) -> Step;
+
+    /// Set the current BDD step
+    fn set_step(&mut self, step: Step);
+}
+
 impl Step {
     /// Initialize BDD Step
     pub fn new() -> Self {
         Step::Start
     }
 
-    /// Get the next BDD Step given a keyword: "Given", "When", "Then" or "And"
+    /// Get the next BDD Step given a keyword: "Given", "When", "Then", "And", "But" or "*", which
+    /// like "And" continues the current step
     pub fn next(self, keyword: &str) -> Option<Step> {
         match keyword {
             "Given" => match self {
@@ -33,7 +47,7 @@ impl Step {
             "Then" => match self {
                 Step::Start | Step::Given | Step::When | Step::Then => Some(Step::Then),
             },
-            "And" => match self {
+            "And" | "But" | "*" => match self {
                 Step::Given | Step::When | Step::Then => Some(self),
                 Step::Start => None,
             },
@@ -42,8 +56,28 @@ impl Step {
     }
 }
 
-impl Default for Step {
-    fn default() -> Self {
-        Step::Start
+impl MatchContext for Step {
+    type Checkpoint = Step;
+
+    #[inline]
+    fn checkpoint(&self) -> Self::Checkpoint {
+        *self
+    }
+
+    #[inline]
+    fn rollback(&mut self, checkpoint: &Self::Checkpoint) {
+        *self = *checkpoint;
+    }
+}
+
+impl StepContext for Step {
+    #[inline]
+    fn step(&self) -> Step {
+        *self
+    }
+
+    #[inline]
+    fn set_step(&mut self, step: Step) {
+        *self = step;
     }
 }
@@ -0,0 +1,399 @@
+//! Doc strings and data tables attached to a step
+
+use crate::matcher::MatchError;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::Peekable;
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::de::{Deserialize, Deserializer, IntoDeserializer, Visitor};
+
+/// The delimiters which open and close a doc string
+const DOC_STRING_DELIMITERS: &[&str] = &["\"\"\"", "```"];
+
+/// A block of text between two `"""` or ```` ``` ```` lines attached to a step. The indentation of
+/// the opening delimiter is removed from every line of the content
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DocString<'a> {
+    /// The media type following the opening delimiter, such as "json"
+    pub media_type: Option<&'a str>,
+    /// The lines between the delimiters
+    pub content: String,
+}
+
+/// A table of pipe-delimited rows such as `| a | b |` attached to a step
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DataTable<'a> {
+    /// The trimmed cells of every row. Every row has the same number of cells
+    pub rows: Vec<Vec<Cow<'a, str>>>,
+}
+
+/// Read the doc string or data table attached to a step. Implemented by the type of the trailing
+/// argument of a step function which is not part of its clause
+pub trait MatchBlock<'a>: Sized {
+    fn match_block(block: Option<&'a str>) -> Result<Self, MatchError>;
+}
+
+/// A cell of a data table which deserializes into numbers, booleans or strings
+struct Cell<'de>(&'de str);
+
+impl<'a> DocString<'a> {
+    /// Parse a doc string starting from the line of its opening delimiter
+    pub fn parse(block: &'a str) -> Option<Self> {
+        let mut lines = block.lines();
+        let opening = lines.next()?;
+        let delimiter = doc_string_delimiter(opening)?;
+        let indentation = opening.len() - opening.trim_start().len();
+        let media_type = opening.trim()[delimiter.len()..].trim();
+        let mut content = Vec::new();
+        for line in &mut lines {
+            if line.trim() == delimiter {
+                return if lines.all(|line| line.trim().is_empty()) {
+                    Some(Self {
+                        media_type: Some(media_type).filter(|m| !m.is_empty()),
+                        content: content.join("\n"),
+                    })
+                } else {
+                    None
+                };
+            }
+            let unindented = line.trim_start();
+            let unindented = &line[(line.len() - unindented.len()).min(indentation)..];
+            content.push(unindented.replace(&format!("\\{}", delimiter), delimiter));
+        }
+        None
+    }
+
+    /// Get the content of the doc string
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+}
+
+impl<'a> DataTable<'a> {
+    /// Parse a data table from its rows. Fails if a row is malformed or has a different number of
+    /// cells than the first
+    pub fn parse(block: &'a str) -> Option<Self> {
+        let rows = block
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(cells)
+            .collect::<Option<Vec<_>>>()?;
+        if rows.iter().any(|row| row.len() != rows[0].len()) {
+            return None;
+        }
+        Some(Self { rows })
+    }
+
+    /// Get the first row of the table
+    #[inline]
+    pub fn header(&self) -> Option<&[Cow<'a, str>]> {
+        self.rows.first().map(Vec::as_slice)
+    }
+
+    /// Deserialize every row into `T`, such as a tuple or a `Vec`, cell by cell
+    pub fn deserialize_rows<'de, T>(&'de self) -> Result<Vec<T>, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        self.rows
+            .iter()
+            .map(|row| T::deserialize(SeqDeserializer::new(row.iter().map(|c| Cell(c)))))
+            .collect()
+    }
+
+    /// Deserialize every row but the first into `T`, such as a struct, keyed by the cells of the
+    /// first row
+    pub fn deserialize_records<'de, T>(&'de self) -> Result<Vec<T>, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        let header = self.header().unwrap_or_default();
+        self.rows
+            .iter()
+            .skip(1)
+            .map(|row| {
+                let entries = header
+                    .iter()
+                    .map(|key| key.as_ref())
+                    .zip(row.iter().map(|c| Cell(c)));
+                T::deserialize(MapDeserializer::new(entries))
+            })
+            .collect()
+    }
+}
+
+impl<'a> MatchBlock<'a> for DocString<'a> {
+    fn match_block(block: Option<&'a str>) -> Result<Self, MatchError> {
+        Self::parse(block.ok_or(MatchError::MissingBlock)?).ok_or(MatchError::InvalidBlock)
+    }
+}
+
+impl<'a> MatchBlock<'a> for DataTable<'a> {
+    fn match_block(block: Option<&'a str>) -> Result<Self, MatchError> {
+        Self::parse(block.ok_or(MatchError::MissingBlock)?).ok_or(MatchError::InvalidBlock)
+    }
+}
+
+impl<'a, T> MatchBlock<'a> for Option<T>
+where
+    T: MatchBlock<'a>,
+{
+    fn match_block(block: Option<&'a str>) -> Result<Self, MatchError> {
+        block.map(|block| T::match_block(Some(block))).transpose()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Cell<'de> {
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Cell<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let cell = self.0;
+        let is_numeric = cell.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c));
+        if let Ok(value) = cell.parse::<i64>() {
+            visitor.visit_i64(value)
+        } else if let Ok(value) = cell.parse::<u64>() {
+            visitor.visit_u64(value)
+        } else if let Some(value) = cell.parse::<f64>().ok().filter(|_| is_numeric) {
+            visitor.visit_f64(value)
+        } else if let Ok(value) = cell.parse::<bool>() {
+            visitor.visit_bool(value)
+        } else {
+            visitor.visit_borrowed_str(cell)
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct ignored_any
+    }
+}
+
+/// Get the delimiter which opens a doc string on a line
+pub(crate) fn doc_string_delimiter(line: &str) -> Option<&'static str> {
+    let line = line.trim_start();
+    DOC_STRING_DELIMITERS
+        .iter()
+        .copied()
+        .find(|delimiter| line.starts_with(delimiter))
+}
+
+/// Check if a line opens a doc string or is a row of a data table
+pub(crate) fn is_block(line: &str) -> bool {
+    doc_string_delimiter(line).is_some() || line.trim_start().starts_with('|')
+}
+
+/// Skip the lines of the block opened by `first`: up to the closing delimiter of a doc string or
+/// the last row of a data table. Returns the last line of the block
+pub(crate) fn skip_block<'a, I>(first: &'a str, lines: &mut Peekable<I>) -> &'a str
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let mut last = first;
+    if let Some(delimiter) = doc_string_delimiter(first) {
+        for (_, line) in lines {
+            last = line;
+            if line.trim() == delimiter {
+                break;
+            }
+        }
+    } else {
+        while let Some((_, line)) = lines.next_if(|(_, line)| line.trim_start().starts_with('|')) {
+            last = line;
+        }
+    }
+    last
+}
+
+/// Split a step into its text and the block attached to it, which starts at the beginning of the
+/// first line opening a block
+pub(crate) fn split_block(step: &str) -> (&str, Option<&str>) {
+    step.match_indices('\n')
+        .map(|(i, _)| i)
+        .find(|&i| is_block(&step[i + 1..]))
+        .map_or((step, None), |i| {
+            (step[..i].trim_end(), Some(&step[i + 1..]))
+        })
+}
+
+/// Get the slice of `string` from the start of `first` to the end of `last` without trailing
+/// whitespace. Both must be slices of `string`
+pub(crate) fn span<'a>(string: &'a str, first: &str, last: &str) -> &'a str {
+    let start = first.as_ptr() as usize - string.as_ptr() as usize;
+    let end = last.as_ptr() as usize - string.as_ptr() as usize + last.trim_end().len();
+    &string[start..end]
+}
+
+/// Split a table row such as `| a | b |` into its trimmed cells. A `|` inside a cell is escaped as
+/// `\|`
+pub(crate) fn cells(line: &str) -> Option<Vec<Cow<'_, str>>> {
+    let inner = line.strip_prefix('|')?;
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let mut is_escaped = false;
+    for (i, c) in inner.char_indices() {
+        match c {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            '|' => {
+                cells.push(unescape_cell(inner[start..i].trim()));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !inner[start..].trim().is_empty() {
+        return None;
+    }
+    Some(cells)
+}
+
+fn unescape_cell(cell: &str) -> Cow<'_, str> {
+    if cell.contains('\\') {
+        Cow::Owned(cell.replace("\\|", "|").replace("\\\\", "\\"))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Account<'a> {
+        name: &'a str,
+        balance: f64,
+        active: bool,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn doc_string() {
+        let doc_string = DocString::parse(
+            "    \"\"\"json\n      {\n        \"a\": 1\n      }\n  \\\"\"\"\n    \"\"\"",
+        )
+        .unwrap();
+        assert_eq!(doc_string.media_type, Some("json"));
+        assert_eq!(doc_string.as_str(), "  {\n    \"a\": 1\n  }\n\"\"\"");
+        assert!(DocString::parse("\"\"\"\nunterminated").is_none());
+        assert!(DocString::parse("| a |").is_none());
+    }
+
+    #[test]
+    fn data_table() -> Result<(), Error> {
+        let table = DataTable::parse(
+            "| name  | balance | active | note |\n\
+             | Alice | 10      | true   |      |\n\
+             | Bob   | -2.5    | false  | new  |",
+        )
+        .unwrap();
+        assert_eq!(
+            table.header().unwrap(),
+            ["name", "balance", "active", "note"]
+        );
+        assert_eq!(
+            table.deserialize_records::<Account>()?,
+            [
+                Account {
+                    name: "Alice",
+                    balance: 10.0,
+                    active: true,
+                    note: None
+                },
+                Account {
+                    name: "Bob",
+                    balance: -2.5,
+                    active: false,
+                    note: Some("new".into())
+                }
+            ]
+        );
+        let rows = table.deserialize_rows::<(String, String, String, String)>()?;
+        assert_eq!(rows[1].1, "10");
+        assert!(table.deserialize_rows::<(String, String)>().is_err());
+        assert!(DataTable::parse("| a | b |\n| c |").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_block("Given the accounts\n  | a |\n  | b |"),
+            ("Given the accounts", Some("  | a |\n  | b |"))
+        );
+        assert_eq!(split_block("Given a | b"), ("Given a | b", None));
+    }
+}
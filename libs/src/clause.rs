@@ -1,5 +1,7 @@
 //! Clause parsing utilities
 
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt;
 
 /// A token parsed from a clause string
@@ -9,103 +11,334 @@ pub enum Token<'a> {
     Static(&'a str),
     /// A query variable
     QueryVar(&'a str),
-    /// A data variable
-    DataVar(&'a str),
+    /// A data variable with an optional type annotation, written `d`name:Type``
+    DataVar(&'a str, Option<&'a str>),
+    /// A raw text variable
+    TextVar(&'a str),
+    /// A list of data variables written as an English enumeration with an optional type annotation
+    /// of its items, written `l`name:Type``
+    ListVar(&'a str, Option<&'a str>),
+    /// An optional group of static words, written `[of the]`
+    Optional(&'a str),
+    /// A group of alternative static phrases, written `(plus|added to)`
+    Alternatives(&'a str),
+}
+
+/// A range of bytes in a clause string
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Span {
+    /// Byte offset of the start of the range
+    pub start: usize,
+    /// Byte offset of the end of the range (exclusive)
+    pub end: usize,
 }
 
 /// An error which may occur during parsing
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ParseError {
     /// Variable name is invalid
-    InvalidVariableName,
-    /// Prefix is invalid. May only be `q` or `d`
-    InvalidVariablePrefix,
+    InvalidVariableName(Span),
+    /// Prefix is invalid. May only be `q`, `d`, `t` or `l`
+    InvalidVariablePrefix(Span),
+    /// A group is empty or is missing its closing bracket
+    InvalidGroup(Span),
+    /// Type annotation of a variable is empty
+    InvalidVariableType(Span),
+    /// A variable name occurs more than once in the clause
+    DuplicateVariable(Span),
+}
+
+impl Span {
+    /// Create a new Span
+    #[inline]
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    #[inline]
+    fn shift(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+}
+
+impl ParseError {
+    /// Get the span of the clause string which caused the error
+    pub fn span(&self) -> Span {
+        match *self {
+            Self::InvalidVariableName(span)
+            | Self::InvalidVariablePrefix(span)
+            | Self::InvalidGroup(span)
+            | Self::InvalidVariableType(span)
+            | Self::DuplicateVariable(span) => span,
+        }
+    }
+
+    fn shift(self, offset: usize) -> Self {
+        match self {
+            Self::InvalidVariableName(span) => Self::InvalidVariableName(span.shift(offset)),
+            Self::InvalidVariablePrefix(span) => Self::InvalidVariablePrefix(span.shift(offset)),
+            Self::InvalidGroup(span) => Self::InvalidGroup(span.shift(offset)),
+            Self::InvalidVariableType(span) => Self::InvalidVariableType(span.shift(offset)),
+            Self::DuplicateVariable(span) => Self::DuplicateVariable(span.shift(offset)),
+        }
+    }
 }
 
 impl<'a> Token<'a> {
     /// Is the token a static token
     pub fn is_static(&self) -> bool {
-        match self {
-            Self::Static(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Static(_))
     }
 
     /// Is the token a query variable
     pub fn is_query_var(&self) -> bool {
-        match self {
-            Self::QueryVar(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::QueryVar(_))
     }
 
     /// Is the token a data variable
     pub fn is_data_var(&self) -> bool {
-        match self {
-            Self::DataVar(_) => true,
-            _ => false,
+        matches!(self, Self::DataVar(..))
+    }
+
+    /// Is the token a raw text variable
+    pub fn is_text_var(&self) -> bool {
+        matches!(self, Self::TextVar(_))
+    }
+
+    /// Is the token a list variable
+    pub fn is_list_var(&self) -> bool {
+        matches!(self, Self::ListVar(..))
+    }
+
+    /// Is the token an optional group
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Self::Optional(_))
+    }
+
+    /// Is the token a group of alternatives
+    pub fn is_alternatives(&self) -> bool {
+        matches!(self, Self::Alternatives(_))
+    }
+
+    /// Get the name of the variable if the token is a variable
+    pub fn var_name(&self) -> Option<&'a str> {
+        match *self {
+            Self::QueryVar(name)
+            | Self::DataVar(name, _)
+            | Self::TextVar(name)
+            | Self::ListVar(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get the type annotation of the variable if the token is an annotated variable
+    pub fn var_type(&self) -> Option<&'a str> {
+        match *self {
+            Self::DataVar(_, ty) | Self::ListVar(_, ty) => ty,
+            _ => None,
+        }
+    }
+}
+
+/// Describe a clause in English with its variables elided, such as "the sum of … henceforth …"
+pub fn describe(clause: &[Token]) -> String {
+    let mut out = String::new();
+    for token in clause {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        match *token {
+            Token::Static(word) => out.extend(unescape(word)),
+            Token::Optional(phrase) => {
+                out.push('[');
+                out.push_str(phrase);
+                out.push(']');
+            }
+            Token::Alternatives(alternatives) => {
+                out.push('(');
+                out.push_str(alternatives);
+                out.push(')');
+            }
+            _ => out.push('…'),
+        }
+    }
+    out
+}
+
+/// Iterate over the characters of a static token with escaped backticks (`\``) unescaped
+pub fn unescape(token: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = token.chars().peekable();
+    core::iter::from_fn(move || match chars.next()? {
+        '\\' if chars.peek() == Some(&'`') => chars.next(),
+        c => Some(c),
+    })
+}
+
+fn has_unescaped_backtick(src: &str) -> bool {
+    let mut escaped = false;
+    for c in src.chars() {
+        match c {
+            '`' if !escaped => return true,
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    false
+}
+
+fn is_valid_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+            chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
         }
+        _ => false,
     }
 }
 
-fn parse_next(src: &str) -> Result<Option<(Token, &str)>, ParseError> {
-    let (tok, rest) = if let Ok((_, tok, rest)) = nl_parser::parse_token(src) {
-        (tok, rest)
+fn parse_typed_var(var: &str, span: Span) -> Result<(&str, Option<&str>), ParseError> {
+    match var.split_once(':') {
+        Some((_, ty)) if ty.trim().is_empty() => Err(ParseError::InvalidVariableType(span)),
+        Some((name, ty)) => Ok((name.trim(), Some(ty.trim()))),
+        None => Ok((var, None)),
+    }
+}
+
+fn parse_group(group: &str, span: Span) -> Result<&str, ParseError> {
+    let group = group.trim();
+    if group.is_empty() || has_unescaped_backtick(group) {
+        Err(ParseError::InvalidGroup(span))
     } else {
-        return Ok(None);
+        Ok(group)
+    }
+}
+
+fn parse_var(tok: &str, span: Span) -> Result<Token<'_>, ParseError> {
+    let mut chars = tok.chars();
+    let prefix = chars.next();
+    if chars.next() != Some('`') || tok.len() < 3 || !tok.ends_with('`') {
+        return Err(ParseError::InvalidVariableName(span));
+    }
+    let var = &tok[2..tok.len() - 1];
+    let (name, token) = match prefix {
+        Some('q') => (var, Token::QueryVar(var)),
+        Some('t') => (var, Token::TextVar(var)),
+        Some('d') => {
+            let (name, ty) = parse_typed_var(var, span)?;
+            (name, Token::DataVar(name, ty))
+        }
+        Some('l') => {
+            let (name, ty) = parse_typed_var(var, span)?;
+            (name, Token::ListVar(name, ty))
+        }
+        _ => return Err(ParseError::InvalidVariablePrefix(span)),
     };
-    if tok.contains('`') {
-        if !tok.ends_with('`') {
-            return Err(ParseError::InvalidVariableName);
-        } else if tok.starts_with("q`") {
-            // TODO check variable name format?
-            let var = &tok[2..tok.len() - 1];
-            return Ok(Some((Token::QueryVar(var), rest)));
-        } else if tok.starts_with("d`") {
-            // TODO check variable name format?
-            let var = &tok[2..tok.len() - 1];
-            return Ok(Some((Token::DataVar(var), rest)));
-        } else {
-            return Err(ParseError::InvalidVariablePrefix);
+    if is_valid_var_name(name) {
+        Ok(token)
+    } else {
+        Err(ParseError::InvalidVariableName(span))
+    }
+}
+
+fn parse_next(src: &str) -> Result<Option<(Token<'_>, Span, &str)>, ParseError> {
+    let start = src.len() - src.trim_start().len();
+    let tok_src = &src[start..];
+    let close = match tok_src.chars().next() {
+        None => return Ok(None),
+        Some('[') => Some(']'),
+        Some('(') => Some(')'),
+        _ => None,
+    };
+    if let Some(close) = close {
+        let len = match tok_src.find(close) {
+            Some(end) => end + close.len_utf8(),
+            None => return Err(ParseError::InvalidGroup(Span::new(start, src.len()))),
+        };
+        let span = Span::new(start, start + len);
+        let group = parse_group(&tok_src[1..len - 1], span)?;
+        let rest = &src[span.end..];
+        if close == ']' {
+            return Ok(Some((Token::Optional(group), span, rest)));
+        }
+        if group.split('|').any(|phrase| phrase.trim().is_empty()) {
+            return Err(ParseError::InvalidGroup(span));
         }
+        return Ok(Some((Token::Alternatives(group), span, rest)));
+    }
+    let len = tok_src.find(char::is_whitespace).unwrap_or(tok_src.len());
+    let tok = &tok_src[..len];
+    let span = Span::new(start, start + len);
+    let rest = &src[span.end..];
+    if has_unescaped_backtick(tok) {
+        Ok(Some((parse_var(tok, span)?, span, rest)))
+    } else if tok.contains(']') || tok.contains(')') {
+        Err(ParseError::InvalidGroup(span))
     } else {
-        return Ok(Some((Token::Static(tok), rest)));
+        Ok(Some((Token::Static(tok), span, rest)))
     }
 }
 
 /// A parser for a clause. Iterates over tokens
 pub struct Parser<'a> {
     src: &'a str,
+    offset: usize,
+    var_names: Vec<&'a str>,
 }
 
 impl<'a> Parser<'a> {
     /// Create a new Parser for a string
     #[inline]
     pub fn new(src: &'a str) -> Self {
-        Self { src }
+        Self {
+            src,
+            offset: 0,
+            var_names: Vec::new(),
+        }
     }
-}
 
-impl<'a> Iterator for Parser<'a> {
-    type Item = Result<Token<'a>, ParseError>;
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Get the next token along with its span in the clause string
+    pub fn next_spanned(&mut self) -> Option<Result<(Token<'a>, Span), ParseError>> {
         match parse_next(self.src) {
-            Ok(Some((tok, rest))) => {
+            Ok(Some((tok, span, rest))) => {
+                let span = span.shift(self.offset);
+                self.offset += self.src.len() - rest.len();
                 self.src = rest;
-                Some(Ok(tok))
+                if let Some(name) = tok.var_name() {
+                    if self.var_names.contains(&name) {
+                        self.src = "";
+                        return Some(Err(ParseError::DuplicateVariable(span)));
+                    }
+                    self.var_names.push(name);
+                }
+                Some(Ok((tok, span)))
             }
             Ok(None) => None,
-            Err(err) => Some(Err(err)),
+            Err(err) => {
+                let err = err.shift(self.offset);
+                self.src = "";
+                Some(Err(err))
+            }
         }
     }
 }
 
+impl<'a> Iterator for Parser<'a> {
+    type Item = Result<Token<'a>, ParseError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_spanned().map(|res| res.map(|(tok, _)| tok))
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ParseError::InvalidVariablePrefix => f.write_str("invalid variable prefix"),
-            ParseError::InvalidVariableName => f.write_str("invalid variable name"),
-        }
+        let msg = match self {
+            ParseError::InvalidVariablePrefix(_) => "invalid variable prefix",
+            ParseError::InvalidVariableName(_) => "invalid variable name",
+            ParseError::InvalidGroup(_) => "invalid group",
+            ParseError::InvalidVariableType(_) => "invalid variable type",
+            ParseError::DuplicateVariable(_) => "duplicate variable",
+        };
+        f.write_fmt(format_args!("{} at byte {}", msg, self.span().start))
     }
 }
 
@@ -115,35 +348,80 @@ pub fn parse<'a>(string: &'a str) -> impl Iterator<Item = Result<Token<'a>, Pars
     Parser::new(string)
 }
 
+/// Parse a string into tokens along with their spans in the string
+pub fn parse_spanned<'a>(
+    string: &'a str,
+) -> impl Iterator<Item = Result<(Token<'a>, Span), ParseError>> {
+    let mut parser = Parser::new(string);
+    core::iter::from_fn(move || parser.next_spanned())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use alloc::vec;
     use alloc::vec::Vec;
 
+    fn next_token(src: &str) -> Result<Option<(Token<'_>, &str)>, ParseError> {
+        Ok(parse_next(src)?.map(|(tok, _, rest)| (tok, rest.trim_start())))
+    }
+
     #[test]
     fn test_parse_next() -> Result<(), ParseError> {
-        assert_eq!(parse_next("the")?, Some((Token::Static("the"), "")));
+        assert_eq!(next_token("the")?, Some((Token::Static("the"), "")));
         assert_eq!(
-            parse_next("the token")?,
+            next_token("the token")?,
             Some((Token::Static("the"), "token"))
         );
         assert_eq!(
-            parse_next("q`variable`")?,
+            next_token("q`variable`")?,
             Some((Token::QueryVar("variable"), ""))
         );
         assert_eq!(
-            parse_next("q`variable` token")?,
+            next_token("q`variable` token")?,
             Some((Token::QueryVar("variable"), "token"))
         );
         assert_eq!(
-            parse_next("d`variable`")?,
-            Some((Token::DataVar("variable"), ""))
+            next_token("d`variable`")?,
+            Some((Token::DataVar("variable", None), ""))
         );
         assert_eq!(
-            parse_next("d`variable` token")?,
-            Some((Token::DataVar("variable"), "token"))
+            next_token("d`variable` token")?,
+            Some((Token::DataVar("variable", None), "token"))
         );
+        assert_eq!(
+            next_token("t`variable` token")?,
+            Some((Token::TextVar("variable"), "token"))
+        );
+        assert_eq!(
+            next_token("l`variable` token")?,
+            Some((Token::ListVar("variable", None), "token"))
+        );
+        assert_eq!(
+            next_token("d`variable:u64` token")?,
+            Some((Token::DataVar("variable", Some("u64")), "token"))
+        );
+        assert_eq!(
+            next_token("l`variable:Option<u8>`")?,
+            Some((Token::ListVar("variable", Some("Option<u8>")), ""))
+        );
+        assert!(next_token("d`variable:`").is_err());
+        assert!(next_token("x`variable`").is_err());
+        assert_eq!(next_token("[the]")?, Some((Token::Optional("the"), "")));
+        assert_eq!(
+            next_token("[ of the ] token")?,
+            Some((Token::Optional("of the"), "token"))
+        );
+        assert!(next_token("[the").is_err());
+        assert!(next_token("[]").is_err());
+        assert!(next_token("the]").is_err());
+        assert_eq!(
+            next_token("(plus|added to) token")?,
+            Some((Token::Alternatives("plus|added to"), "token"))
+        );
+        assert!(next_token("(plus|)").is_err());
+        assert!(next_token("(plus").is_err());
+        assert!(next_token("plus)").is_err());
         Ok(())
     }
 
@@ -169,10 +447,91 @@ mod tests {
             parse("the d`variable` token").collect::<Result<Vec<Token>, ParseError>>()?,
             vec![
                 Token::Static("the"),
-                Token::DataVar("variable"),
+                Token::DataVar("variable", None),
+                Token::Static("token")
+            ]
+        );
+        assert_eq!(
+            parse("[the] q`variable` [of the] token")
+                .collect::<Result<Vec<Token>, ParseError>>()?,
+            vec![
+                Token::Optional("the"),
+                Token::QueryVar("variable"),
+                Token::Optional("of the"),
                 Token::Static("token")
             ]
         );
+        assert_eq!(
+            parse("q`a` (plus | added to) d`b`").collect::<Result<Vec<Token>, ParseError>>()?,
+            vec![
+                Token::QueryVar("a"),
+                Token::Alternatives("plus | added to"),
+                Token::DataVar("b", None)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_spanned() -> Result<(), ParseError> {
+        assert_eq!(
+            parse_spanned("the  q`variable` [of the]")
+                .collect::<Result<Vec<(Token, Span)>, ParseError>>()?,
+            vec![
+                (Token::Static("the"), Span::new(0, 3)),
+                (Token::QueryVar("variable"), Span::new(5, 16)),
+                (Token::Optional("of the"), Span::new(17, 25))
+            ]
+        );
         Ok(())
     }
+
+    #[test]
+    fn test_parse_errors() {
+        let first_err = |src| parse(src).find_map(Result::err);
+        assert_eq!(
+            first_err("the q`1variable` token"),
+            Some(ParseError::InvalidVariableName(Span::new(4, 16)))
+        );
+        assert_eq!(
+            first_err("the x`variable`"),
+            Some(ParseError::InvalidVariablePrefix(Span::new(4, 15)))
+        );
+        assert_eq!(
+            first_err("q`a` the d`a`"),
+            Some(ParseError::DuplicateVariable(Span::new(9, 13)))
+        );
+        assert_eq!(
+            first_err("the [of the"),
+            Some(ParseError::InvalidGroup(Span::new(4, 11)))
+        );
+        assert!(first_err("q`has space`").is_some());
+        assert!(first_err("q`a:u8`").is_some());
+        let mut parser = parse("x`a` the");
+        assert!(matches!(parser.next(), Some(Err(_))));
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_escaped_backticks() -> Result<(), ParseError> {
+        assert_eq!(
+            parse(r"the \`quoted\` d`value`").collect::<Result<Vec<Token>, ParseError>>()?,
+            vec![
+                Token::Static("the"),
+                Token::Static(r"\`quoted\`"),
+                Token::DataVar("value", None)
+            ]
+        );
+        assert!(unescape(r"\`quoted\`").eq("`quoted`".chars()));
+        assert!(unescape(r"a\b").eq(r"a\b".chars()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe() {
+        let clause = parse(r"the \`sum\` [of the] q`a` (plus|and) d`b`")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(describe(&clause), "the `sum` [of the] … (plus|and) …");
+    }
 }
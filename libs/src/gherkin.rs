@@ -0,0 +1,484 @@
+//! Gherkin documents: a feature made of scenarios, each of which compiles into its own `Script`
+
+use crate::block::{cells, is_block, skip_block, span};
+use crate::matcher::{MatchError, Matcher, Mismatch, Policy};
+use crate::module::{Module, ModuleType};
+use crate::tags::TagExpression;
+use crate::vm::{Func, Script};
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::vec::Vec;
+use core::mem;
+
+/// A numbered step of a scenario
+pub type Step<'a> = (usize, Cow<'a, str>);
+
+/// A Gherkin feature and its scenarios
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Feature<'a> {
+    /// The name of the feature
+    pub name: &'a str,
+    /// The tags of the feature, such as "@smoke"
+    pub tags: Vec<&'a str>,
+    /// The free text lines between the name of the feature and its first section
+    pub description: Vec<&'a str>,
+    /// The scenarios of the feature. Scenario outlines are expanded into one scenario per row of
+    /// examples
+    pub scenarios: Vec<Scenario<'a>>,
+}
+
+/// A scenario of a feature
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Scenario<'a> {
+    /// The name of the scenario, with the placeholders of an outline filled in
+    pub name: Cow<'a, str>,
+    /// The number of the line which declares the scenario, or of its row of examples
+    pub line: usize,
+    /// The tags of the scenario, following the tags of its feature and followed by the tags of its
+    /// examples
+    pub tags: Vec<&'a str>,
+    /// The steps of the scenario, starting with the steps of the background of the feature
+    pub steps: Vec<Step<'a>>,
+}
+
+/// The keyword which starts a section of a feature
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Keyword {
+    Feature,
+    Background,
+    Scenario,
+    Outline,
+    Examples,
+}
+
+/// The section of a feature being parsed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Section {
+    Start,
+    Feature,
+    Background,
+    Scenario,
+    Outline,
+    Examples,
+}
+
+/// A scenario outline waiting for its examples
+#[derive(Default)]
+struct Outline<'a> {
+    name: Cow<'a, str>,
+    tags: Vec<&'a str>,
+    example_tags: Vec<&'a str>,
+    steps: Vec<Step<'a>>,
+    header: Option<Vec<Cow<'a, str>>>,
+}
+
+/// The keywords which start a step
+const STEP_KEYWORDS: &[&str] = &["Given", "When", "Then", "And", "But", "*"];
+
+impl<'a> Feature<'a> {
+    /// Parse a Gherkin document. Returns the number of the offending line on failure
+    pub fn parse(string: &'a str) -> Result<Self, (usize, MatchError)> {
+        let mut feature = Feature::default();
+        let mut background = Vec::new();
+        let mut outline = Outline::default();
+        let mut section = Section::Start;
+        let mut tags = Vec::new();
+        let mut lines = string.lines().enumerate().peekable();
+        while let Some((line_num, raw_line)) = lines.next() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |expected: &str| {
+                let mismatch = Matcher::new(line).mismatch(expected);
+                (line_num, MatchError::InvalidFeature(mismatch))
+            };
+            if line.starts_with('@') {
+                parse_tags(line, &mut tags)
+                    .map_err(|mismatch| (line_num, MatchError::InvalidFeature(mismatch)))?;
+                continue;
+            }
+            let is_taggable = matches!(
+                keyword(line),
+                Some((Keyword::Feature, _))
+                    | Some((Keyword::Scenario, _))
+                    | Some((Keyword::Outline, _))
+                    | Some((Keyword::Examples, _))
+            );
+            if !tags.is_empty() && !is_taggable {
+                return Err(invalid("Feature:, Scenario: or Examples: after tags"));
+            }
+            section = match (keyword(line), section) {
+                (Some((Keyword::Feature, name)), Section::Start) => {
+                    feature.name = name;
+                    feature.tags = mem::take(&mut tags);
+                    Section::Feature
+                }
+                (_, Section::Start) => return Err(invalid("Feature:")),
+                (Some((Keyword::Background, _)), Section::Feature) => Section::Background,
+                (Some((Keyword::Scenario, name)), _) => {
+                    let tags = [&feature.tags[..], &mem::take(&mut tags)].concat();
+                    feature.scenarios.push(Scenario {
+                        name: Cow::Borrowed(name),
+                        line: line_num,
+                        tags,
+                        steps: background.clone(),
+                    });
+                    Section::Scenario
+                }
+                (Some((Keyword::Outline, name)), _) => {
+                    outline = Outline {
+                        name: Cow::Borrowed(name),
+                        tags: [&feature.tags[..], &mem::take(&mut tags)].concat(),
+                        ..Outline::default()
+                    };
+                    Section::Outline
+                }
+                (Some((Keyword::Examples, _)), Section::Outline)
+                | (Some((Keyword::Examples, _)), Section::Examples) => {
+                    outline.header = None;
+                    outline.example_tags = mem::take(&mut tags);
+                    Section::Examples
+                }
+                (Some(_), _) => return Err(invalid("Scenario:")),
+                (None, Section::Examples) => {
+                    let row = cells(line).ok_or_else(|| invalid("|"))?;
+                    match outline.header {
+                        None => outline.header = Some(row),
+                        Some(ref header) if header.len() == row.len() => feature
+                            .scenarios
+                            .push(outline.expand(line_num, &background, &row)),
+                        Some(ref header) => {
+                            return Err(invalid(&format!("a row of {} cells", header.len())))
+                        }
+                    }
+                    Section::Examples
+                }
+                (None, Section::Feature) => {
+                    feature.description.push(line);
+                    Section::Feature
+                }
+                (None, section) if is_block(line) => {
+                    let last = skip_block(raw_line, &mut lines);
+                    let step = match section {
+                        Section::Background => background.last_mut().map(|(_, step)| step),
+                        Section::Outline => outline.steps.last_mut().map(|(_, step)| step),
+                        _ => feature
+                            .scenarios
+                            .last_mut()
+                            .filter(|scenario| scenario.steps.len() > background.len())
+                            .and_then(|scenario| scenario.steps.last_mut())
+                            .map(|(_, step)| step),
+                    };
+                    match step {
+                        Some(Cow::Borrowed(step)) => *step = span(string, step, last),
+                        _ => return Err(invalid("a step before the doc string or data table")),
+                    }
+                    section
+                }
+                (None, section) => {
+                    let steps = match section {
+                        Section::Background => background.len(),
+                        Section::Outline => outline.steps.len(),
+                        _ => {
+                            feature.scenarios.last().map_or(0, |s| s.steps.len()) - background.len()
+                        }
+                    };
+                    if !is_step(line) {
+                        if steps > 0 {
+                            return Err(invalid(
+                                "a step starting with Given, When, Then, And, But or *",
+                            ));
+                        }
+                        // free text describing the section
+                        continue;
+                    }
+                    match section {
+                        Section::Background => background.push((line_num, Cow::Borrowed(line))),
+                        Section::Outline => outline.steps.push((line_num, Cow::Borrowed(line))),
+                        _ => {
+                            if let Some(scenario) = feature.scenarios.last_mut() {
+                                scenario.steps.push((line_num, Cow::Borrowed(line)));
+                            }
+                        }
+                    }
+                    section
+                }
+            };
+        }
+        Ok(feature)
+    }
+
+    /// Keep only the scenarios whose tags match an expression
+    pub fn retain(&mut self, expression: &TagExpression) {
+        self.scenarios
+            .retain(|scenario| expression.matches(&scenario.tags));
+    }
+
+    /// Compile every scenario with a module, each with a new context
+    pub fn compile<'s, M, C>(&'s self, module: &M) -> Result<Vec<Script<'s>>, (usize, M::Error)>
+    where
+        M: Module<'s, C>,
+        C: Default,
+    {
+        self.compile_with(module, &Policy::default())
+    }
+
+    /// Compile every scenario with a module and a policy, each with a new context
+    pub fn compile_with<'s, M, C>(
+        &'s self,
+        module: &M,
+        policy: &Policy,
+    ) -> Result<Vec<Script<'s>>, (usize, M::Error)>
+    where
+        M: Module<'s, C>,
+        C: Default,
+    {
+        self.scenarios
+            .iter()
+            .map(|scenario| scenario.compile_with(module, &mut C::default(), policy))
+            .collect()
+    }
+
+    /// Compile every scenario with a module type, each with a new context
+    pub fn compile_type<'s, M, C>(&'s self) -> Result<Vec<Script<'s>>, (usize, M::Error)>
+    where
+        M: ModuleType<'s, C>,
+        C: Default,
+    {
+        self.compile_type_with::<M, C>(&Policy::default())
+    }
+
+    /// Compile every scenario with a module type and a policy, each with a new context
+    pub fn compile_type_with<'s, M, C>(
+        &'s self,
+        policy: &Policy,
+    ) -> Result<Vec<Script<'s>>, (usize, M::Error)>
+    where
+        M: ModuleType<'s, C>,
+        C: Default,
+    {
+        self.scenarios
+            .iter()
+            .map(|scenario| scenario.compile_type_with::<M, C>(&mut C::default(), policy))
+            .collect()
+    }
+}
+
+impl<'a> Scenario<'a> {
+    /// Compile the scenario into a Script titled with its name
+    pub fn compile<'s, M, C>(
+        &'s self,
+        module: &M,
+        ctx: &mut C,
+    ) -> Result<Script<'s>, (usize, M::Error)>
+    where
+        M: Module<'s, C>,
+    {
+        self.compile_with(module, ctx, &Policy::default())
+    }
+
+    /// Compile the scenario into a Script titled with its name, matching its steps with a policy
+    pub fn compile_with<'s, M, C>(
+        &'s self,
+        module: &M,
+        ctx: &mut C,
+        policy: &Policy,
+    ) -> Result<Script<'s>, (usize, M::Error)>
+    where
+        M: Module<'s, C>,
+    {
+        self.compile_steps(policy, |matcher| module.compile_matcher(ctx, matcher))
+    }
+
+    /// Compile the scenario into a Script titled with its name using a module type
+    pub fn compile_type<'s, M, C>(&'s self, ctx: &mut C) -> Result<Script<'s>, (usize, M::Error)>
+    where
+        M: ModuleType<'s, C>,
+    {
+        self.compile_type_with::<M, C>(ctx, &Policy::default())
+    }
+
+    /// Compile the scenario into a Script titled with its name using a module type, matching its
+    /// steps with a policy
+    pub fn compile_type_with<'s, M, C>(
+        &'s self,
+        ctx: &mut C,
+        policy: &Policy,
+    ) -> Result<Script<'s>, (usize, M::Error)>
+    where
+        M: ModuleType<'s, C>,
+    {
+        self.compile_steps(policy, |matcher| M::compile_matcher(ctx, matcher))
+    }
+
+    fn compile_steps<'s, E, F>(
+        &'s self,
+        policy: &Policy,
+        mut compile: F,
+    ) -> Result<Script<'s>, (usize, E)>
+    where
+        F: FnMut(Matcher<'s>) -> Result<Func<'s>, E>,
+    {
+        let mut funcs = Vec::new();
+        for (line_num, step) in &self.steps {
+            let matcher = Matcher::with_policy(step, policy.clone());
+            funcs.push(compile(matcher).map_err(|e| (*line_num, e))?);
+        }
+        let mut script = Script::from(funcs);
+        script.metadata_mut().title = Some(&self.name);
+        script.metadata_mut().tags = self.tags.clone();
+        Ok(script)
+    }
+}
+
+impl<'a> Outline<'a> {
+    /// Create the scenario for a row of examples by filling in the placeholders of the outline
+    fn expand(&self, line: usize, background: &[Step<'a>], row: &[Cow<'a, str>]) -> Scenario<'a> {
+        let header = self.header.as_deref().unwrap_or_default();
+        let mut steps = background.to_vec();
+        steps.extend(
+            self.steps
+                .iter()
+                .map(|(line_num, step)| (*line_num, fill(step, header, row))),
+        );
+        Scenario {
+            name: fill(&self.name, header, row),
+            line,
+            tags: [&self.tags[..], &self.example_tags].concat(),
+            steps,
+        }
+    }
+}
+
+/// Read the tags of a line such as `@smoke @slow`, up to a comment
+pub(crate) fn parse_tags<'a>(line: &'a str, tags: &mut Vec<&'a str>) -> Result<(), Mismatch> {
+    let mut matcher = Matcher::new(line);
+    loop {
+        let start = matcher.clone();
+        match matcher.next_static() {
+            Ok(tag) if tag.starts_with('#') => return Ok(()),
+            Ok(tag) if tag.len() > 1 && tag.starts_with('@') => tags.push(tag),
+            Ok(_) => return Err(start.mismatch("@tag")),
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+/// Get the keyword which starts a section and the name which follows it
+fn keyword(line: &str) -> Option<(Keyword, &str)> {
+    const KEYWORDS: &[(&str, Keyword)] = &[
+        ("Feature:", Keyword::Feature),
+        ("Background:", Keyword::Background),
+        ("Scenario Outline:", Keyword::Outline),
+        ("Scenario Template:", Keyword::Outline),
+        ("Scenario:", Keyword::Scenario),
+        ("Example:", Keyword::Scenario),
+        ("Examples:", Keyword::Examples),
+        ("Scenarios:", Keyword::Examples),
+    ];
+    KEYWORDS.iter().find_map(|(prefix, keyword)| {
+        line.strip_prefix(prefix)
+            .map(|name| (*keyword, name.trim()))
+    })
+}
+
+/// Check if a line starts with a step keyword
+fn is_step(line: &str) -> bool {
+    let word = line.split_whitespace().next().unwrap_or_default();
+    STEP_KEYWORDS.contains(&word)
+}
+
+/// Replace the `<placeholders>` of a line with the cells of a row of examples
+fn fill<'a>(line: &Cow<'a, str>, header: &[Cow<'a, str>], row: &[Cow<'a, str>]) -> Cow<'a, str> {
+    if !line.contains('<') {
+        return line.clone();
+    }
+    let mut filled = line.clone().into_owned();
+    for (name, value) in header.iter().zip(row) {
+        filled = filled.replace(&format!("<{}>", name), value);
+    }
+    Cow::Owned(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_feature() -> Result<(), (usize, MatchError)> {
+        let feature = Feature::parse(
+            r#"
+            # a comment
+            Feature: Addition
+              Adding numbers
+
+              Background:
+                Given the input
+
+              Scenario: Add one
+                Some free text
+                Given the addition of 1
+                Then done
+
+              Scenario Outline: Add <n>
+                Given the addition of <n>
+                But not <m>
+
+                Examples:
+                  | n | m     |
+                  | 2 | 3     |
+                  | 4 | a \| b |
+            "#,
+        )?;
+        assert_eq!(feature.name, "Addition");
+        assert_eq!(feature.description, ["Adding numbers"]);
+        assert_eq!(feature.scenarios.len(), 3);
+        let scenario = &feature.scenarios[0];
+        assert_eq!(scenario.name, "Add one");
+        assert_eq!(scenario.line, 8);
+        assert_eq!(
+            scenario.steps,
+            [
+                (6, Cow::Borrowed("Given the input")),
+                (10, Cow::Borrowed("Given the addition of 1")),
+                (11, Cow::Borrowed("Then done"))
+            ]
+        );
+        let scenario = &feature.scenarios[2];
+        assert_eq!(scenario.name, "Add 4");
+        assert_eq!(scenario.line, 20);
+        assert_eq!(
+            scenario.steps,
+            [
+                (6, Cow::Borrowed("Given the input")),
+                (14, Cow::Borrowed("Given the addition of 4")),
+                (15, Cow::Borrowed("But not a | b"))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_feature() {
+        for (src, line, expected) in &[
+            ("Given the input", 0, "Feature:"),
+            ("Feature: A\nExamples:", 1, "Scenario:"),
+            ("Feature: A\nScenario: B\nGiven x\nfree text", 3, "a step"),
+            (
+                "Feature: A\nScenario Outline: B\nGiven <x>\nExamples:\n| x |\n| 1 | 2 |",
+                5,
+                "a row of 1 cells",
+            ),
+            ("Feature: A\nScenario Outline: B\nExamples:\nx", 3, "|"),
+        ] {
+            match Feature::parse(src) {
+                Err((err_line, MatchError::InvalidFeature(mismatch))) => {
+                    assert_eq!(err_line, *line);
+                    assert!(mismatch.expected.starts_with(expected));
+                }
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Including the steps of other scripts with lines such as "Include the steps from `setup`"
+
+use crate::matcher::{MatchContext, MatchError, Matcher, Policy};
+use crate::module::{by_priority, compile_matcher_checked, parse_header, steps, FuncDef, Module};
+use crate::vm::{Func, Metadata, Script};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+#[cfg(feature = "std")]
+use std::cell::{OnceCell, RefCell};
+#[cfg(feature = "std")]
+use std::path::{Component, Path, PathBuf};
+#[cfg(feature = "std")]
+use std::{fs, io};
+
+/// The phrase which starts a line including another script
+const INCLUDE: &str = "Include the steps from";
+
+/// Finds the source of a script included by name
+pub trait Resolver<'a> {
+    fn resolve(&self, name: &str) -> Result<&'a str, MatchError>;
+}
+
+/// A `Resolver` over sources held in memory
+#[derive(Clone, Debug, Default)]
+pub struct MemoryResolver {
+    sources: BTreeMap<String, String>,
+}
+
+/// A `Resolver` over the files below a root directory, named by their path relative to it such as
+/// "shared/setup.ogma". Files are looked up and read when they are first included, so a file
+/// created after the resolver is found as well. Names may not leave the root directory
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FileResolver {
+    root: PathBuf,
+    files: Files,
+}
+
+/// The sources of the files read by a `FileResolver`, indexed by name. Sources are added behind a
+/// shared reference into segments of 1, 2, 4, … slots which are never moved, so that sources
+/// already handed out stay in place. Every file read is kept until the resolver is dropped
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct Files {
+    segments: [OnceCell<Box<[OnceCell<String>]>>; SEGMENTS],
+    names: RefCell<BTreeMap<String, usize>>,
+}
+
+/// The number of segments of `Files`, enough for any number of sources
+#[cfg(feature = "std")]
+const SEGMENTS: usize = usize::BITS as usize;
+
+/// A `Module` which compiles the steps of the scripts included by a script in place of the line
+/// including them. Included scripts may include further scripts, but not themselves. A line
+/// compiled on its own, such as a step of a `PhraseScript`, which includes a script compiles into
+/// a `Func` calling the steps of the included script in turn
+pub struct WithResolver<M, R> {
+    module: M,
+    resolver: R,
+}
+
+/// A step of a script and the include lines through which it was reached
+struct Step<'a> {
+    line_num: usize,
+    line: &'a str,
+    includes: Vec<(usize, &'a str)>,
+}
+
+impl MemoryResolver {
+    /// Create an empty MemoryResolver
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the source of a script under a name
+    pub fn insert(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.sources.insert(name.into(), source.into());
+    }
+}
+
+#[cfg(feature = "std")]
+impl FileResolver {
+    /// Create a FileResolver over the files below an existing root directory
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        Ok(Self {
+            root: root.into().canonicalize()?,
+            files: Files::default(),
+        })
+    }
+
+    /// Get the canonical path of the root directory
+    #[inline]
+    pub fn root(&self) -> &PathBuf {
+        &self.root
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Files {
+    fn default() -> Self {
+        Self {
+            segments: core::array::from_fn(|_| OnceCell::new()),
+            names: RefCell::default(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Files {
+    /// Get the source of a file which was read before
+    fn get(&self, name: &str) -> Option<&str> {
+        let index = *self.names.borrow().get(name)?;
+        self.slot(index).get().map(String::as_str)
+    }
+
+    /// Add the source of a file in the next free slot
+    fn insert(&self, name: String, source: String) -> &str {
+        let index = self.names.borrow().len();
+        let source = self.slot(index).get_or_init(|| source);
+        self.names.borrow_mut().insert(name, index);
+        source
+    }
+
+    /// Get the slot of the source with an index. Segment `k` holds the sources from `2^k - 1`
+    fn slot(&self, index: usize) -> &OnceCell<String> {
+        let segment = (usize::BITS - 1 - (index + 1).leading_zeros()) as usize;
+        let slots = self.segments[segment]
+            .get_or_init(|| (0..1usize << segment).map(|_| OnceCell::new()).collect());
+        &slots[index + 1 - (1 << segment)]
+    }
+}
+
+impl<M, R> WithResolver<M, R> {
+    /// Wrap a module to compile included scripts found by a resolver
+    pub fn new(module: M, resolver: R) -> Self {
+        Self { module, resolver }
+    }
+
+    /// Get the wrapped module
+    #[inline]
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    /// Get the resolver
+    #[inline]
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+}
+
+impl<'a, M, R> WithResolver<M, R>
+where
+    R: Resolver<'a>,
+{
+    /// Collect the steps of a script and of the scripts it includes. Returns the metadata of the
+    /// script
+    fn expand(
+        &self,
+        string: &'a str,
+        policy: &Policy,
+        includes: &mut Vec<(usize, &'a str)>,
+        out: &mut Vec<Step<'a>>,
+    ) -> Result<Metadata<'a>, (usize, MatchError)> {
+        let (metadata, start) = parse_header(string)?;
+        for (line_num, line) in steps(string, start) {
+            let name = match include_name(Matcher::with_policy(line, policy.clone())) {
+                Some(name) => name,
+                None => {
+                    out.push(Step {
+                        line_num,
+                        line,
+                        includes: includes.clone(),
+                    });
+                    continue;
+                }
+            };
+            if includes.iter().any(|(_, included)| *included == name) {
+                return Err((line_num, MatchError::CyclicInclude(name.into())));
+            }
+            let source = self.resolver.resolve(name).map_err(|e| (line_num, e))?;
+            includes.push((line_num, name));
+            self.expand(source, policy, includes, out)
+                .map_err(|(line, e)| {
+                    (
+                        line_num,
+                        MatchError::Included(name.into(), line, Box::new(e)),
+                    )
+                })?;
+            includes.pop();
+        }
+        Ok(metadata)
+    }
+
+    /// Collect the steps of a script and of the scripts it includes
+    fn expand_all(
+        &self,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<(Metadata<'a>, Vec<Step<'a>>), (usize, MatchError)> {
+        let mut steps = Vec::new();
+        let metadata = self.expand(string, policy, &mut Vec::new(), &mut steps)?;
+        Ok((metadata, steps))
+    }
+}
+
+impl<'a> Step<'a> {
+    /// Locate an error of the step in the script which includes it
+    fn locate(&self, err: MatchError) -> (usize, MatchError) {
+        self.includes.iter().rev().fold(
+            (self.line_num, err),
+            |(line, err), (include_line, name)| {
+                let err = MatchError::Included(name.to_string(), line, Box::new(err));
+                (*include_line, err)
+            },
+        )
+    }
+}
+
+impl<'a> Resolver<'a> for &'a MemoryResolver {
+    fn resolve(&self, name: &str) -> Result<&'a str, MatchError> {
+        let resolver: &'a MemoryResolver = self;
+        resolver
+            .sources
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| MatchError::UnresolvedInclude(name.into()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Resolver<'a> for &'a FileResolver {
+    fn resolve(&self, name: &str) -> Result<&'a str, MatchError> {
+        let resolver: &'a FileResolver = self;
+        let unresolved = || MatchError::UnresolvedInclude(name.into());
+        let mut parts = Vec::new();
+        for component in Path::new(name).components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_string_lossy()),
+                Component::CurDir => {}
+                _ => return Err(unresolved()),
+            }
+        }
+        if parts.is_empty() {
+            return Err(unresolved());
+        }
+        let file = parts.join("/");
+        if let Some(source) = resolver.files.get(&file) {
+            return Ok(source);
+        }
+        let unreadable = |err: io::Error| match err.kind() {
+            io::ErrorKind::NotFound => unresolved(),
+            _ => MatchError::UnreadableInclude(name.into(), err.to_string()),
+        };
+        let path = resolver
+            .root
+            .join(&file)
+            .canonicalize()
+            .map_err(unreadable)?;
+        if !path.starts_with(&resolver.root) {
+            return Err(unresolved());
+        }
+        let source = fs::read_to_string(path).map_err(unreadable)?;
+        Ok(resolver.files.insert(file, source))
+    }
+}
+
+impl<'a, C, M, R> Module<'a, C> for WithResolver<M, R>
+where
+    M: Module<'a, C>,
+    M::Error: Into<MatchError>,
+    R: Resolver<'a>,
+{
+    type Error = MatchError;
+    fn compile_matcher(&self, ctx: &mut C, matcher: Matcher<'a>) -> Result<Func<'a>, Self::Error> {
+        let name = match include_name(matcher.clone()) {
+            Some(name) => name,
+            None => {
+                return self
+                    .module
+                    .compile_matcher(ctx, matcher)
+                    .map_err(Into::into)
+            }
+        };
+        let source = self.resolver.resolve(name)?;
+        let script = self
+            .compile_with(ctx, source, matcher.policy())
+            .map_err(|(line, err)| MatchError::Included(name.into(), line, Box::new(err)))?;
+        Ok(Box::new(script))
+    }
+    fn compile_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, (usize, Self::Error)> {
+        let (metadata, steps) = self.expand_all(string, policy)?;
+        let mut script = Vec::new();
+        for step in steps {
+            let matcher = Matcher::with_policy(step.line, policy.clone());
+            let func = self
+                .module
+                .compile_matcher(ctx, matcher)
+                .map_err(|e| step.locate(e.into()))?;
+            script.push(func);
+        }
+        let mut script = Script::from(script);
+        *script.metadata_mut() = metadata;
+        Ok(script)
+    }
+    fn definitions(&self) -> &[FuncDef<'a, C>] {
+        self.module.definitions()
+    }
+    fn compile_checked_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+    {
+        let definitions = self.definitions();
+        let (metadata, steps) = self.expand_all(string, policy).map_err(|err| vec![err])?;
+        let mut script = Vec::new();
+        let mut errors = Vec::new();
+        for step in steps {
+            let matcher = Matcher::with_policy(step.line, policy.clone());
+            let func = if definitions.is_empty() {
+                self.module
+                    .compile_matcher(ctx, matcher)
+                    .map_err(Into::into)
+            } else {
+                compile_matcher_checked(by_priority(definitions), ctx, matcher)
+            };
+            match func {
+                Ok(func) => script.push(func),
+                Err(err) => errors.push(step.locate(err)),
+            }
+        }
+        if errors.is_empty() {
+            let mut script = Script::from(script);
+            *script.metadata_mut() = metadata;
+            Ok(script)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl<N, S> FromIterator<(N, S)> for MemoryResolver
+where
+    N: Into<String>,
+    S: Into<String>,
+{
+    fn from_iter<T: IntoIterator<Item = (N, S)>>(iter: T) -> Self {
+        let mut resolver = Self::new();
+        for (name, source) in iter {
+            resolver.insert(name, source);
+        }
+        resolver
+    }
+}
+
+/// Get the name of the script included by a line such as "Include the steps from `setup`"
+fn include_name(mut matcher: Matcher<'_>) -> Option<&str> {
+    matcher.next_phrase(INCLUDE).ok()?;
+    let name = matcher.next_raw_text(None).ok()?;
+    Some(
+        name.strip_prefix('`')
+            .and_then(|name| name.strip_suffix('`'))
+            .unwrap_or(name),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn include_names() {
+        let name = |line| include_name(Matcher::new(line));
+        assert_eq!(
+            name("Include the steps from `shared/setup`"),
+            Some("shared/setup")
+        );
+        assert_eq!(name("Include the steps from setup"), Some("setup"));
+        assert_eq!(name("Include the steps"), None);
+        assert_eq!(name("Given the steps from setup"), None);
+    }
+
+    #[test]
+    fn include_errors() {
+        let resolver: MemoryResolver = vec![
+            ("a", "Include the steps from `b`"),
+            ("b", "\nInclude the steps from `a`"),
+        ]
+        .into_iter()
+        .collect();
+        let module = WithResolver::new(Vec::<FuncDef<()>>::new(), &resolver);
+        let (line_num, err) = module
+            .compile(&mut (), "Include the steps from `a`")
+            .err()
+            .unwrap();
+        assert_eq!(line_num, 0);
+        assert_eq!(
+            err.to_string(),
+            "in 'a' at line 1: in 'b' at line 2: script 'a' includes itself"
+        );
+        let (line_num, err) = module
+            .compile(&mut (), "\n\nInclude the steps from `c`")
+            .err()
+            .unwrap();
+        assert_eq!(line_num, 2);
+        assert_eq!(err.to_string(), "could not find the included script 'c'");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn files() {
+        let files = Files::default();
+        let sources = (0..100)
+            .map(|i| files.insert(i.to_string(), format!("source {}", i)))
+            .collect::<Vec<_>>();
+        for (i, source) in sources.into_iter().enumerate() {
+            assert_eq!(source, format!("source {}", i));
+            assert_eq!(files.get(&i.to_string()), Some(source));
+        }
+        assert_eq!(files.get("100"), None);
+    }
+}
@@ -6,7 +6,16 @@ extern crate std as core;
 extern crate alloc;
 
 pub mod bdd;
+pub mod block;
 pub mod clause;
+pub mod gherkin;
+pub mod include;
 pub mod matcher;
 pub mod module;
+pub mod number;
+pub mod phrase;
+pub mod quantity;
+pub mod tags;
+#[cfg(feature = "time")]
+pub mod time;
 pub mod vm;
@@ -1,11 +1,20 @@
 //! Function matching utilities
 
+use crate::block::split_block;
+use crate::clause::{describe, unescape, Token};
+use crate::number::{is_primitive, Number};
 use crate::vm::{Callable, Func};
+use alloc::borrow::Cow;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt;
+use core::iter::FromIterator;
 use object_query::Query;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 
 /// An error which can occur during matchng
 #[derive(Debug)]
@@ -13,46 +22,597 @@ pub enum MatchError {
     /// NLSD parse error
     Nlsd(nlsd::Error),
     /// Token mismatch
-    MismatchedStaticToken,
+    MismatchedStaticToken(Mismatch),
     /// Missing query
     EmptyQuery,
     /// Mismatched query Name
     UnknownQueryVar,
     /// Mismatched data Name
     UnknownDataVar,
+    /// Mismatched text Name
+    UnknownTextVar,
+    /// Mismatched list Name
+    UnknownListVar,
+    /// List is not a valid English enumeration
+    InvalidList,
+    /// Data could not be read by a `MatchData` implementation
+    InvalidData,
+    /// Quantity has a unit which is not known
+    UnknownUnit(Mismatch),
+    /// Quantity has a unit of a different dimension than expected
+    IncompatibleUnit(Mismatch),
+    /// A relative date or time was read without a clock in the match context
+    MissingClock,
     /// Var left empty
     UnfilledVar,
     /// Expected more tokens to match against
-    UnexpectedEof,
+    UnexpectedEof(Mismatch),
     /// Matching has finished but there is still more string
-    ExpectedEof,
+    ExpectedEof(Mismatch),
+    /// A variable could not be read from the string
+    InvalidVar(Mismatch, Box<MatchError>),
+    /// No function of a module matched the line. Candidates are ordered by how far they matched
+    NoMatch(Vec<Candidate>),
+    /// More than one function of a module with the same priority matched the line
+    Ambiguous(Vec<&'static [Token<'static>]>),
     /// Invalid matching context
     InvalidCtx,
+    /// The front-matter header of a script is malformed
+    InvalidHeader(Mismatch),
+    /// A Gherkin document is malformed
+    InvalidFeature(Mismatch),
+    /// A step function expects a doc string or data table but the step has none
+    MissingBlock,
+    /// A step has a doc string or data table but its function does not expect one
+    UnexpectedBlock,
+    /// The block of a step is not the expected doc string or data table
+    InvalidBlock,
+    /// A tag expression is malformed
+    InvalidTagExpression(Mismatch),
+    /// The source of an included script could not be found
+    UnresolvedInclude(String),
+    /// The source of an included script was found but could not be read. Holds the name of the
+    /// script and the reason
+    UnreadableInclude(String, String),
+    /// A script includes itself, directly or through other scripts
+    CyclicInclude(String),
+    /// A line of an included script failed to compile. Holds the name of the script and the
+    /// number of the line in it
+    Included(String, usize, Box<MatchError>),
+    /// The definition of a phrase in a script is malformed
+    InvalidPhrase(Mismatch),
+    /// A step of the body of a phrase failed to compile. Holds the description of the phrase and
+    /// the number of the line of the step
+    InPhrase(String, usize, Box<MatchError>),
+}
+
+/// Where a match failed in a line, what was expected and what was found instead
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Mismatch {
+    /// Byte offset in the line
+    pub offset: usize,
+    /// Line within a step which continues over multiple lines, starting from 0
+    pub line: usize,
+    /// Column in the text of the step, starting from 1 at the first non-whitespace character of
+    /// the line. The indentation of the line in the script is not counted, so for an indented
+    /// script the column differs from the one shown by an editor
+    pub column: usize,
+    /// The expected static token or variable name. Empty if the end of the line was expected
+    pub expected: String,
+    /// The token found instead. Empty at the end of the line
+    pub found: String,
+}
+
+/// A function which failed to match a line and why
+#[derive(Debug)]
+pub struct Candidate {
+    /// The clause of the function. Empty if the function has no clause
+    pub clause: &'static [Token<'static>],
+    /// The error which occurred while matching the function
+    pub error: MatchError,
+}
+
+/// Sentence punctuation which may be ignored at the end of a token
+pub(crate) const PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?'];
+
+/// Invisible characters which separate tokens under a lenient policy
+const INVISIBLE: &[char] = &['\u{200b}', '\u{2060}', '\u{feff}'];
+
+/// Controls how leniently a `Matcher` compares a string against static tokens. The default policy
+/// compares tokens byte for byte
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    /// Compare static tokens ignoring case
+    pub ignore_case: bool,
+    /// Ignore sentence punctuation trailing a static token or the end of the string
+    pub ignore_punctuation: bool,
+    /// Also treat invisible characters, such as zero-width spaces, as separators between tokens.
+    /// Unicode whitespace such as non-breaking spaces always separates tokens
+    pub collapse_whitespace: bool,
+    /// Words which may be used interchangeably with a static token
+    pub synonyms: Arc<Synonyms>,
+}
+
+/// A table of interchangeable words. Words are compared case insensitively
+#[derive(Clone, Debug, Default)]
+pub struct Synonyms {
+    groups: BTreeMap<String, usize>,
+    next_group: usize,
 }
 
 /// Reads tokens, queries and data from a string
+#[derive(Clone)]
 pub struct Matcher<'a> {
+    line: &'a str,
     src: &'a str,
+    block: Option<&'a str>,
+    policy: Policy,
+    before_and: bool,
+}
+
+impl Policy {
+    /// Create a strict policy
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a policy which ignores case, trailing punctuation and unusual whitespace
+    pub fn lenient() -> Self {
+        Self {
+            ignore_case: true,
+            ignore_punctuation: true,
+            collapse_whitespace: true,
+            synonyms: Arc::default(),
+        }
+    }
+
+    /// Set the synonyms used to compare static tokens
+    pub fn with_synonyms(mut self, synonyms: Synonyms) -> Self {
+        self.synonyms = Arc::new(synonyms);
+        self
+    }
+
+    /// Check if a token found in the string is equal to a static token
+    pub fn token_eq(&self, found: &str, expected: &str) -> bool {
+        let found = if self.ignore_punctuation {
+            found.trim_end_matches(PUNCTUATION)
+        } else {
+            found
+        };
+        let is_eq = if self.ignore_case {
+            found
+                .chars()
+                .flat_map(char::to_lowercase)
+                .eq(unescape(expected).flat_map(char::to_lowercase))
+        } else if expected.contains('\\') {
+            found.chars().eq(unescape(expected))
+        } else {
+            found == expected
+        };
+        is_eq || self.synonyms.are_synonyms(found, expected)
+    }
+
+    fn is_separator(&self, c: char) -> bool {
+        c.is_whitespace() || self.collapse_whitespace && INVISIBLE.contains(&c)
+    }
+
+    /// Skip the separators at the start of a string, including a `\` which continues a step on
+    /// the following line
+    fn skip_separators<'s>(&self, src: &'s str) -> &'s str {
+        let mut src = src.trim_start_matches(|c| self.is_separator(c));
+        while is_continuation(src) {
+            src = src[1..].trim_start_matches(|c| self.is_separator(c));
+        }
+        src
+    }
+
+    /// Get the length of the token at the start of a string
+    fn token_len(&self, src: &str) -> usize {
+        src.char_indices()
+            .find(|&(i, c)| self.is_separator(c) || is_continuation(&src[i..]))
+            .map_or(src.len(), |(i, _)| i)
+    }
+}
+
+impl Synonyms {
+    /// Create an empty synonym table
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a group of words as synonyms of one another. Groups which share a word are merged
+    pub fn insert<I, S>(&mut self, words: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let words = words
+            .into_iter()
+            .map(|word| word.as_ref().to_lowercase())
+            .collect::<Vec<_>>();
+        let existing = words
+            .iter()
+            .filter_map(|word| self.groups.get(word).copied())
+            .collect::<Vec<_>>();
+        let group = if let Some(group) = existing.first() {
+            *group
+        } else {
+            self.next_group += 1;
+            self.next_group
+        };
+        for other in self.groups.values_mut() {
+            if existing.contains(other) {
+                *other = group;
+            }
+        }
+        for word in words {
+            self.groups.insert(word, group);
+        }
+    }
+
+    /// Check if the table has no words
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Check if two words belong to the same group
+    pub fn are_synonyms(&self, a: &str, b: &str) -> bool {
+        if self.groups.is_empty() {
+            return false;
+        }
+        match (
+            self.groups.get(&a.to_lowercase()),
+            self.groups.get(&b.to_lowercase()),
+        ) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl<'a> Matcher<'a> {
     /// Create a new Matcher instance
+    #[inline]
     pub fn new(src: &'a str) -> Self {
-        Self { src }
+        Self::with_policy(src, Policy::default())
+    }
+
+    /// Create a new Matcher instance which compares static tokens according to a `Policy`. A doc
+    /// string or data table on the lines following the step is set aside as its block
+    pub fn with_policy(src: &'a str, policy: Policy) -> Self {
+        let (src, block) = split_block(src);
+        let src = trim_end(src, &policy);
+        Self {
+            line: src,
+            src,
+            block,
+            policy,
+            before_and: false,
+        }
+    }
+
+    /// Match the rest of the string according to another `Policy`
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.src = trim_end(self.src, &policy);
+        self.policy = policy;
+    }
+
+    /// Get the rest of the string which is left to match
+    #[inline]
+    pub fn rest(&self) -> &'a str {
+        self.src
+    }
+
+    /// Check if the variable being read is followed by "and" in its clause, so that it should not
+    /// read an "and" of its own
+    #[inline]
+    pub(crate) fn is_before_and(&self) -> bool {
+        self.before_and
+    }
+
+    /// Get the matching policy
+    #[inline]
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Describe a mismatch at the current position of the matcher. The column is relative to the
+    /// text of the step, without the indentation of its line
+    pub fn mismatch(&self, expected: &str) -> Mismatch {
+        let src = self.policy.skip_separators(self.src);
+        let offset = src.as_ptr() as usize - self.line.as_ptr() as usize;
+        let found = &src[..self.policy.token_len(src)];
+        let before = &self.line[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Mismatch {
+            offset,
+            line: before.matches('\n').count(),
+            column: before[line_start..].trim_start().chars().count() + 1,
+            expected: expected.into(),
+            found: found.into(),
+        }
     }
 
     /// Get the next static token from the string
     pub fn next_static(&mut self) -> Result<&'a str, MatchError> {
-        if let Ok((_, tok, rest)) = nl_parser::parse_token(self.src) {
-            self.src = rest;
-            Ok(tok)
+        let src = self.policy.skip_separators(self.src);
+        if src.is_empty() {
+            return Err(MatchError::UnexpectedEof(self.mismatch("")));
+        }
+        let end = self.policy.token_len(src);
+        self.src = self.policy.skip_separators(&src[end..]);
+        Ok(&src[..end])
+    }
+
+    /// Match the next static token against a word of a clause
+    pub fn next_word(&mut self, word: &str) -> Result<(), MatchError> {
+        let start = self.clone();
+        match self.next_static() {
+            Ok(found) if self.policy.token_eq(found, word) => Ok(()),
+            Ok(_) => Err(MatchError::MismatchedStaticToken(start.mismatch(word))),
+            Err(_) => Err(MatchError::UnexpectedEof(start.mismatch(word))),
+        }
+    }
+
+    /// Match every word of a phrase against the next static tokens
+    pub fn next_phrase(&mut self, phrase: &str) -> Result<(), MatchError> {
+        for word in phrase.split_whitespace() {
+            self.next_word(word)?;
+        }
+        Ok(())
+    }
+
+    /// Match a phrase if it is present. If the phrase does not match nothing is consumed
+    pub fn next_optional(&mut self, phrase: &str) -> bool {
+        let mut next = self.clone();
+        if next.next_phrase(phrase).is_ok() {
+            *self = next;
+            true
         } else {
-            Err(MatchError::UnexpectedEof)
+            false
         }
     }
 
-    /// Get the next NLOQ query from the string
+    /// Match the first of a list of phrases separated by `|` which matches the next static tokens
+    /// and return the phrase which matched
+    pub fn next_alternative<'s>(&mut self, alternatives: &'s str) -> Result<&'s str, MatchError> {
+        for phrase in alternatives.split('|') {
+            let mut next = self.clone();
+            if next.next_phrase(phrase).is_ok() {
+                *self = next;
+                return Ok(phrase.trim());
+            }
+        }
+        if self.is_empty() {
+            Err(MatchError::UnexpectedEof(self.mismatch(alternatives)))
+        } else {
+            Err(MatchError::MismatchedStaticToken(
+                self.mismatch(alternatives),
+            ))
+        }
+    }
+
+    /// Match a static token, optional group or group of alternatives of a clause
+    pub fn next_token(&mut self, token: &Token) -> Result<(), MatchError> {
+        match *token {
+            Token::Static(word) => self.next_word(word),
+            Token::Optional(phrase) => self.next_phrase(phrase),
+            Token::Alternatives(alternatives) => self.next_alternative(alternatives).map(|_| ()),
+            _ => Err(MatchError::MismatchedStaticToken(
+                self.mismatch(token.var_name().unwrap_or_default()),
+            )),
+        }
+    }
+
+    /// Get the raw text up to the following token of a clause. If there is no following token the
+    /// rest of the string is returned. The text contains at least one word. Lines continued with
+    /// `\` are joined with a single space, so only continued text is copied
+    pub fn next_text(&mut self, next: Option<&Token>) -> Result<Cow<'a, str>, MatchError> {
+        self.next_raw_text(next).map(join_lines)
+    }
+
+    /// Get the text up to the following token of a clause as it is written in the step
+    pub(crate) fn next_raw_text(&mut self, next: Option<&Token>) -> Result<&'a str, MatchError> {
+        let src = self.src;
+        self.next_static()?;
+        if let Some(next) = next {
+            loop {
+                if self.clone().next_token(next).is_ok() {
+                    break;
+                }
+                if self.next_static().is_err() {
+                    break;
+                }
+            }
+        } else {
+            self.src = &self.src[self.src.len()..];
+        }
+        Ok(src[..src.len() - self.src.len()].trim())
+    }
+
+    /// Get the English enumeration up to the following token of a clause, such as "a, b and c",
+    /// "a or b" or "either a or b", and read each item into `T`
+    #[inline]
+    pub fn next_list<T>(&mut self, next: Option<&Token>) -> Result<Vec<T>, MatchError>
+    where
+        T: MatchData<'a>,
+    {
+        self.next_list_in(&(), next)
+    }
+
+    /// Get the English enumeration up to the following token of a clause and read each item into
+    /// `T` depending on the match context
+    #[inline]
+    pub fn next_list_in<C, T>(
+        &mut self,
+        ctx: &C,
+        next: Option<&Token>,
+    ) -> Result<Vec<T>, MatchError>
+    where
+        T: MatchDataIn<'a, C>,
+    {
+        self.next_list_with(next, |matcher| matcher.next_data_in(ctx))
+    }
+
+    /// Get the English enumeration up to the following token of a clause and read each item with
+    /// a parser
+    pub fn next_list_with<T, F>(
+        &mut self,
+        next: Option<&Token>,
+        mut parse: F,
+    ) -> Result<Vec<T>, MatchError>
+    where
+        F: FnMut(&mut Matcher<'a>) -> Result<T, MatchError>,
+    {
+        split_list(self.next_raw_text(next)?)?
+            .into_iter()
+            .map(|item| {
+                let mut matcher = Matcher {
+                    line: self.line,
+                    src: item,
+                    block: None,
+                    policy: self.policy.clone(),
+                    before_and: false,
+                };
+                let out = parse(&mut matcher)?;
+                if matcher.is_empty() {
+                    Ok(out)
+                } else {
+                    Err(MatchError::InvalidList)
+                }
+            })
+            .collect()
+    }
+
+    /// Match every token of a clause, calling `f` with the index of each token and a matcher
+    /// positioned at it. Query variables are given a matcher over a candidate prefix of the string
+    /// which they must consume entirely. Candidates end before an occurrence of the following
+    /// token and are tried from longest to shortest until the rest of the clause matches. A query
+    /// which is not followed by a static token may end at any word, so the time taken grows
+    /// exponentially with the number of such queries in a row; clauses should separate queries
+    /// with static words
+    pub fn next_clause<F>(&mut self, clause: &[Token], f: &mut F) -> Result<(), MatchError>
+    where
+        F: FnMut(usize, &mut Matcher<'a>) -> Result<(), MatchError>,
+    {
+        self.next_clause_from(clause, 0, f)
+    }
+
+    fn next_clause_from<F>(
+        &mut self,
+        clause: &[Token],
+        index: usize,
+        f: &mut F,
+    ) -> Result<(), MatchError>
+    where
+        F: FnMut(usize, &mut Matcher<'a>) -> Result<(), MatchError>,
+    {
+        let token = match clause.get(index) {
+            Some(token) => token,
+            None if self.is_empty() => return Ok(()),
+            None => return Err(MatchError::ExpectedEof(self.mismatch(""))),
+        };
+        if !token.is_query_var() {
+            let start = self.clone();
+            self.before_and = matches!(
+                clause.get(index + 1),
+                Some(Token::Static(word)) if self.policy.token_eq("and", word)
+            );
+            let res = f(index, self);
+            self.before_and = false;
+            res.map_err(|err| start.var_error(token, err))?;
+            return self.next_clause_from(clause, index + 1, f);
+        }
+        let mut err = self.var_error(token, MatchError::EmptyQuery);
+        for end in self.query_candidates(clause.get(index + 1)) {
+            let mut query = Matcher::with_policy(&self.src[..end], self.policy.clone());
+            query.line = self.line;
+            let mut rest = Matcher {
+                line: self.line,
+                src: &self.src[end..],
+                block: self.block,
+                policy: self.policy.clone(),
+                before_and: false,
+            };
+            let res = f(index, &mut query)
+                .and_then(|_| {
+                    if query.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(MatchError::ExpectedEof(query.mismatch("")))
+                    }
+                })
+                .map_err(|err| self.var_error(token, err))
+                .and_then(|_| rest.next_clause_from(clause, index + 1, f));
+            match res {
+                Ok(()) => {
+                    *self = rest;
+                    return Ok(());
+                }
+                Err(e) => err = furthest(err, e),
+            }
+        }
+        Err(err)
+    }
+
+    /// Attach the name of a variable to an error which occurred while reading it
+    fn var_error(&self, token: &Token, err: MatchError) -> MatchError {
+        let name = match token.var_name() {
+            Some(name) => name,
+            None => return err,
+        };
+        match err {
+            MatchError::UnexpectedEof(mut mismatch) => {
+                mismatch.expected = name.into();
+                MatchError::UnexpectedEof(mismatch)
+            }
+            err @ MatchError::MismatchedStaticToken(_)
+            | err @ MatchError::UnknownUnit(_)
+            | err @ MatchError::IncompatibleUnit(_)
+            | err @ MatchError::InvalidVar(..) => err,
+            _ if self.is_empty() => MatchError::UnexpectedEof(self.mismatch(name)),
+            err => MatchError::InvalidVar(self.mismatch(name), Box::new(err)),
+        }
+    }
+
+    /// Get the byte offsets at which a query followed by `next` may end, longest first. If `next`
+    /// occurs nowhere the query is read greedily so that the mismatch is reported after it
+    fn query_candidates(&self, next: Option<&Token>) -> Vec<usize> {
+        let next = match next {
+            Some(next) if next.is_static() || next.is_alternatives() => Some(next),
+            Some(_) => None,
+            None => return vec![self.src.len()],
+        };
+        let mut candidates = Vec::new();
+        let mut words = self.clone();
+        while let Ok(word) = words.next_static() {
+            let ends_query = match next {
+                Some(next) => words.clone().next_token(next).is_ok(),
+                None => true,
+            };
+            if ends_query {
+                candidates.push(word.as_ptr() as usize - self.src.as_ptr() as usize + word.len());
+            }
+        }
+        if candidates.is_empty() {
+            let mut greedy = self.clone();
+            if greedy.next_query().is_ok() {
+                candidates.push(self.src.len() - greedy.src.len());
+            }
+        }
+        candidates.reverse();
+        candidates
+    }
+
+    /// Get the next NLOQ query from the string. A query continued over several lines is read from
+    /// its joined lines into owned keys
     pub fn next_query(&mut self) -> Result<Vec<Query<'a>>, MatchError> {
+        if find_continuation(self.src).is_some() {
+            return self.next_continued_query();
+        }
         let mut nloq_de = nloq::Deserializer::from_str(self.src);
         let query = nloq_de.query();
         if query.is_empty() {
@@ -63,6 +623,34 @@ impl<'a> Matcher<'a> {
         }
     }
 
+    /// Get the next NLOQ query from the joined lines of the string, advancing past the part of the
+    /// string it was read from
+    fn next_continued_query(&mut self) -> Result<Vec<Query<'a>>, MatchError> {
+        let lines = continued_lines(self.src);
+        let joined = lines
+            .iter()
+            .map(|&(_, line)| line)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut nloq_de = nloq::Deserializer::from_str(&joined);
+        let query = nloq_de.query();
+        if query.is_empty() {
+            return Err(MatchError::EmptyQuery);
+        }
+        let query = query.into_iter().map(|q| q.to_owned()).collect();
+        let mut consumed = joined.len() - nloq_de.rest().len();
+        let mut end = self.src.len();
+        for &(offset, line) in &lines {
+            if consumed <= line.len() {
+                end = offset + consumed;
+                break;
+            }
+            consumed -= line.len() + 1;
+        }
+        self.src = &self.src[end..];
+        Ok(query)
+    }
+
     /// Get the next NLOQ query from the string
     pub fn next_query_owned(&mut self) -> Result<Vec<Query<'static>>, MatchError> {
         Ok(self
@@ -72,62 +660,694 @@ impl<'a> Matcher<'a> {
             .collect())
     }
 
-    /// Get the next NLSD object from the string and deserialize into `T`
+    /// Read the next data variable from the string into `T`
+    #[inline]
     pub fn next_data<T>(&mut self) -> Result<T, MatchError>
     where
-        T: Deserialize<'a>,
+        T: MatchData<'a>,
     {
-        let mut nlsd_de = nlsd::Deserializer::from_str(self.src);
-        let out = T::deserialize(&mut nlsd_de)?;
-        self.src = nlsd_de.rest();
-        Ok(out)
+        T::match_data(self)
+    }
+
+    /// Read the next data variable from the string into `T` depending on the match context
+    #[inline]
+    pub fn next_data_in<C, T>(&mut self, ctx: &C) -> Result<T, MatchError>
+    where
+        T: MatchDataIn<'a, C>,
+    {
+        T::match_data_in(ctx, self)
+    }
+
+    /// Take the doc string or data table attached to the step
+    #[inline]
+    pub fn next_block(&mut self) -> Option<&'a str> {
+        self.block.take()
     }
 
     /// Check if the matcher contains more tokens
     pub fn is_empty(&self) -> bool {
-        self.src.trim_start().is_empty()
+        self.policy.skip_separators(self.src).is_empty()
     }
 }
 
 /// Create `Self` from a string slice given a context. This should be implemented by functions that
-/// wish to be compileable and read their arguments from an English string
+/// wish to be compileable and read their arguments from an English string. A failed match should
+/// leave the context unchanged as the next function of a module is matched with the same context
 pub trait Match<'a, C>: Sized {
     fn match_str(ctx: &mut C, string: &'a str) -> Result<Self, MatchError>;
+
+    /// The clause matched by `Self`, used to suggest close matches when no function matches
+    fn clause() -> &'static [Token<'static>] {
+        &[]
+    }
+
+    /// The priority of `Self` over other functions of a module which match the same line
+    fn priority() -> i32 {
+        0
+    }
+
+    /// Create `Self` from the remaining string of a `Matcher`. Implementors should override this
+    /// in order to honor the `Policy` of the `Matcher`
+    fn match_with(ctx: &mut C, matcher: Matcher<'a>) -> Result<Self, MatchError> {
+        Self::match_str(ctx, matcher.src)
+    }
+}
+
+/// Types which can read themselves from the string of a `Matcher` as the value of a data variable,
+/// such as "5 EUR" or "account #42". Types which implement `Deserialize` are read from NLSD, or
+/// as a `Number` if they are primitive numbers. A variable of such a type can be read with another
+/// parser through the `parse(name = parser)` option of `ogma_fn`
+pub trait MatchData<'a>: Sized {
+    fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError>;
+}
+
+impl<'a, T> MatchData<'a> for T
+where
+    T: Deserialize<'a>,
+{
+    fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        if is_primitive::<T>() {
+            let mut number = matcher.clone();
+            if let Ok(Ok(out)) = Number::match_data(&mut number).map(Number::deserialize) {
+                *matcher = number;
+                return Ok(out);
+            }
+        }
+        let src = matcher.policy.skip_separators(matcher.src);
+        let mut nlsd_de = nlsd::Deserializer::from_str(src);
+        let out = T::deserialize(&mut nlsd_de)?;
+        matcher.src = nlsd_de.rest();
+        Ok(out)
+    }
+}
+
+/// Types which read themselves from the string of a `Matcher` depending on the match context `C`,
+/// such as dates relative to a clock. Every `MatchData` type is read regardless of the context
+pub trait MatchDataIn<'a, C>: Sized {
+    fn match_data_in(ctx: &C, matcher: &mut Matcher<'a>) -> Result<Self, MatchError>;
+}
+
+impl<'a, C, T> MatchDataIn<'a, C> for T
+where
+    T: MatchData<'a>,
+{
+    #[inline]
+    fn match_data_in(_: &C, matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        T::match_data(matcher)
+    }
+}
+
+/// A match context whose state can be saved before matching a candidate and restored afterwards,
+/// so that every candidate of a line is matched against the same state
+pub trait MatchContext {
+    /// The saved state of the context
+    type Checkpoint;
+
+    /// Save the current state of the context
+    fn checkpoint(&self) -> Self::Checkpoint;
+
+    /// Restore the context to a saved state
+    fn rollback(&mut self, checkpoint: &Self::Checkpoint);
+}
+
+impl MatchContext for () {
+    type Checkpoint = ();
+
+    #[inline]
+    fn checkpoint(&self) -> Self::Checkpoint {}
+
+    #[inline]
+    fn rollback(&mut self, _: &Self::Checkpoint) {}
+}
+
+/// Implements `MatchContext` for `Clone` types by saving a clone of the context
+///
+/// ```skip
+/// ogma::match_context!(MyContext, MyOtherContext);
+/// ```
+#[macro_export]
+macro_rules! match_context {
+    ($($ctx:ty),*) => {
+        $(
+            impl $crate::matcher::MatchContext for $ctx {
+                type Checkpoint = $ctx;
+
+                #[inline]
+                fn checkpoint(&self) -> Self::Checkpoint {
+                    ::core::clone::Clone::clone(self)
+                }
+
+                #[inline]
+                fn rollback(&mut self, checkpoint: &Self::Checkpoint) {
+                    ::core::clone::Clone::clone_from(self, checkpoint);
+                }
+            }
+        )*
+    };
 }
 
 /// A function pointer which matches a string given a context to a callable Func
-pub type FuncMatcher<'a, C> = fn(&mut C, &'a str) -> Result<Func<'a>, MatchError>;
+pub type FuncMatcher<'a, C> = fn(&mut C, Matcher<'a>) -> Result<Func<'a>, MatchError>;
 
 /// Auto implemented trait for types which both implement `Match` and `Callable`
 pub trait MatchFunc<'a, C>: 'a + Match<'a, C> + Callable {
-    fn match_func(ctx: &mut C, string: &'a str) -> Result<Func<'a>, MatchError> {
-        Self::match_str(ctx, string).map(|this| Box::new(this) as Box<dyn Callable>)
+    fn match_func(ctx: &mut C, matcher: Matcher<'a>) -> Result<Func<'a>, MatchError> {
+        Self::match_with(ctx, matcher).map(|this| Box::new(this) as Box<dyn Callable>)
     }
 }
 
 impl<'a, C, T> MatchFunc<'a, C> for T where T: 'a + Match<'a, C> + Callable {}
 
+/// Remove the separators and, if the policy ignores it, the punctuation at the end of a string
+fn trim_end<'s>(src: &'s str, policy: &Policy) -> &'s str {
+    let src = src.trim_end_matches(|c| policy.is_separator(c));
+    if policy.ignore_punctuation {
+        src.trim_end_matches(PUNCTUATION)
+    } else {
+        src
+    }
+}
+
+/// Check if a string starts with a `\` which ends its line and so continues a step on the
+/// following line
+fn is_continuation(src: &str) -> bool {
+    match src.strip_prefix('\\') {
+        Some(rest) => matches!(
+            rest.trim_start_matches(|c: char| c != '\n' && c.is_whitespace())
+                .chars()
+                .next(),
+            None | Some('\n')
+        ),
+        None => false,
+    }
+}
+
+/// Find the `\` which continues the first continued line of a string
+fn find_continuation(src: &str) -> Option<usize> {
+    src.match_indices('\\')
+        .map(|(index, _)| index)
+        .find(|&index| is_continuation(&src[index..]))
+}
+
+/// Split a string continued over several lines into its lines, without the `\` which continues
+/// each line and the indentation of the following one, paired with their offsets in the string.
+/// Empty lines are skipped
+fn continued_lines(src: &str) -> Vec<(usize, &str)> {
+    let mut lines = Vec::new();
+    let mut rest = src;
+    while let Some(index) = find_continuation(rest) {
+        lines.push((src.len() - rest.len(), rest[..index].trim_end()));
+        rest = rest[index + 1..].trim_start();
+    }
+    lines.push((src.len() - rest.len(), rest));
+    lines.retain(|(_, line)| !line.is_empty());
+    lines
+}
+
+/// Join the lines of a string continued over several lines with a single space
+fn join_lines(src: &str) -> Cow<'_, str> {
+    if find_continuation(src).is_none() {
+        return Cow::Borrowed(src);
+    }
+    match continued_lines(src)[..] {
+        [] => Cow::Borrowed(""),
+        [(_, line)] => Cow::Borrowed(line),
+        ref lines => Cow::Owned(
+            lines
+                .iter()
+                .map(|&(_, line)| line)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    }
+}
+
+/// Split an English enumeration into its items. Items may be separated by commas, "and" or "or"
+/// but "and" and "or" may not be mixed
+fn split_list(src: &str) -> Result<Vec<&str>, MatchError> {
+    let (src, mut conjunction) = match src.split_once(char::is_whitespace) {
+        Some(("either", rest)) => (rest, Some("or")),
+        Some(("both", rest)) => (rest, Some("and")),
+        _ => (src, None),
+    };
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut word_start = None;
+    for (i, c) in src.char_indices().chain(core::iter::once((src.len(), ' '))) {
+        if in_string {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '`' if !escaped => in_string = false,
+                _ => escaped = false,
+            }
+            continue;
+        }
+        match c {
+            '`' => in_string = true,
+            ',' => {
+                push_list_item(&mut items, &src[start..i]);
+                start = i + 1;
+                word_start = None;
+            }
+            c if c.is_whitespace() => {
+                if let Some(word_start) = word_start.take() {
+                    let word = &src[word_start..i];
+                    if word == "and" || word == "or" {
+                        if conjunction.get_or_insert(word) != &word {
+                            return Err(MatchError::InvalidList);
+                        }
+                        push_list_item(&mut items, &src[start..word_start]);
+                        start = i;
+                    }
+                }
+            }
+            _ => {
+                if word_start.is_none() {
+                    word_start = Some(i);
+                }
+            }
+        }
+    }
+    if in_string {
+        return Err(MatchError::InvalidList);
+    }
+    push_list_item(&mut items, &src[start..]);
+    if items.is_empty() {
+        Err(MatchError::InvalidList)
+    } else {
+        Ok(items)
+    }
+}
+
+fn push_list_item<'a>(items: &mut Vec<&'a str>, item: &'a str) {
+    let item = item.trim();
+    if !item.is_empty() {
+        items.push(item);
+    }
+}
+
+impl<I, S> FromIterator<I> for Synonyms
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    fn from_iter<T: IntoIterator<Item = I>>(iter: T) -> Self {
+        let mut synonyms = Self::new();
+        for words in iter {
+            synonyms.insert(words);
+        }
+        synonyms
+    }
+}
+
+impl<'de> Deserialize<'de> for Synonyms {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<Vec<String>>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+impl MatchError {
+    /// Get the clause of the candidate which matched furthest, if any
+    pub fn suggestion(&self) -> Option<&'static [Token<'static>]> {
+        match self {
+            Self::NoMatch(candidates) => candidates
+                .iter()
+                .map(|candidate| candidate.clause)
+                .find(|clause| !clause.is_empty()),
+            _ => None,
+        }
+    }
+
+    /// Get where in the line the error occurred, if known
+    pub fn mismatch(&self) -> Option<&Mismatch> {
+        match self {
+            Self::MismatchedStaticToken(mismatch)
+            | Self::UnexpectedEof(mismatch)
+            | Self::ExpectedEof(mismatch)
+            | Self::UnknownUnit(mismatch)
+            | Self::IncompatibleUnit(mismatch)
+            | Self::InvalidHeader(mismatch)
+            | Self::InvalidFeature(mismatch)
+            | Self::InvalidTagExpression(mismatch)
+            | Self::InvalidPhrase(mismatch)
+            | Self::InvalidVar(mismatch, _) => Some(mismatch),
+            _ => None,
+        }
+    }
+}
+
+impl Candidate {
+    /// Create a new Candidate
+    pub fn new(clause: &'static [Token<'static>], error: MatchError) -> Self {
+        Self { clause, error }
+    }
+
+    /// Get how far into the line the candidate matched
+    pub fn offset(&self) -> usize {
+        self.error.mismatch().map_or(0, |mismatch| mismatch.offset)
+    }
+}
+
+/// Pick the error which occurred furthest into the line
+fn furthest(a: MatchError, b: MatchError) -> MatchError {
+    let offset = |err: &MatchError| err.mismatch().map(|mismatch| mismatch.offset);
+    if offset(&b) >= offset(&a) {
+        b
+    } else {
+        a
+    }
+}
+
 impl From<nlsd::Error> for MatchError {
     fn from(err: nlsd::Error) -> Self {
         Self::Nlsd(err)
     }
 }
 
+impl Mismatch {
+    /// Describe the line and column of the mismatch
+    fn position(&self) -> Position<'_> {
+        Position(self)
+    }
+}
+
+/// The line and column of a `Mismatch`
+struct Position<'m>(&'m Mismatch);
+
+impl<'m> fmt::Display for Position<'m> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.line > 0 {
+            f.write_fmt(format_args!("line {} of the step, ", self.0.line + 1))?;
+        }
+        f.write_fmt(format_args!("column {}", self.0.column))
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.expected.is_empty() {
+            f.write_str("expected end of line")?;
+        } else {
+            f.write_fmt(format_args!("expected '{}'", self.expected))?;
+        }
+        f.write_fmt(format_args!(" at {}, ", self.position()))?;
+        if self.found.is_empty() {
+            f.write_str("found end of line")
+        } else {
+            f.write_fmt(format_args!("found '{}'", self.found))
+        }
+    }
+}
+
 impl fmt::Display for MatchError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Nlsd(err) => f.write_fmt(format_args!("NLSD err: {}", err)),
-            Self::MismatchedStaticToken => f.write_str("mismatched static token"),
+            Self::MismatchedStaticToken(mismatch) => mismatch.fmt(f),
             Self::EmptyQuery => f.write_str("empty NLOQ query"),
             Self::UnknownQueryVar => f.write_str("mismatched query variable name"),
             Self::UnknownDataVar => f.write_str("mismatched data variable name"),
+            Self::UnknownTextVar => f.write_str("mismatched text variable name"),
+            Self::UnknownListVar => f.write_str("mismatched list variable name"),
+            Self::InvalidList => f.write_str("invalid list"),
+            Self::InvalidData => f.write_str("invalid data"),
+            Self::UnknownUnit(mismatch) => f.write_fmt(format_args!(
+                "unknown unit '{}' at {}, expected a unit of {}",
+                mismatch.found,
+                mismatch.position(),
+                mismatch.expected
+            )),
+            Self::IncompatibleUnit(mismatch) => f.write_fmt(format_args!(
+                "incompatible unit '{}' at {}, expected a unit of {}",
+                mismatch.found,
+                mismatch.position(),
+                mismatch.expected
+            )),
+            Self::MissingClock => f.write_str("relative date or time without a clock"),
             Self::UnfilledVar => f.write_str("variable not set"),
-            Self::UnexpectedEof => f.write_str("unexpected end of file"),
-            Self::ExpectedEof => f.write_str("clause has extra tokens"),
+            Self::UnexpectedEof(mismatch) => mismatch.fmt(f),
+            Self::ExpectedEof(mismatch) => mismatch.fmt(f),
+            Self::InvalidVar(mismatch, err) => f.write_fmt(format_args!(
+                "invalid {} at {}: {}",
+                mismatch.expected,
+                mismatch.position(),
+                err
+            )),
             Self::InvalidCtx => f.write_str("context error when parsing"),
+            Self::InvalidHeader(mismatch) => {
+                f.write_fmt(format_args!("invalid script header: {}", mismatch))
+            }
+            Self::InvalidFeature(mismatch) => {
+                f.write_fmt(format_args!("invalid feature: {}", mismatch))
+            }
+            Self::MissingBlock => f.write_str("expected a doc string or data table"),
+            Self::UnexpectedBlock => f.write_str("unexpected doc string or data table"),
+            Self::InvalidBlock => f.write_str("invalid doc string or data table"),
+            Self::InvalidTagExpression(mismatch) => {
+                f.write_fmt(format_args!("invalid tag expression: {}", mismatch))
+            }
+            Self::UnresolvedInclude(name) => f.write_fmt(format_args!(
+                "could not find the included script '{}'",
+                name
+            )),
+            Self::UnreadableInclude(name, reason) => f.write_fmt(format_args!(
+                "could not read the included script '{}': {}",
+                name, reason
+            )),
+            Self::CyclicInclude(name) => {
+                f.write_fmt(format_args!("script '{}' includes itself", name))
+            }
+            Self::Included(name, line, err) => {
+                f.write_fmt(format_args!("in '{}' at line {}: {}", name, line + 1, err))
+            }
+            Self::InvalidPhrase(mismatch) => {
+                f.write_fmt(format_args!("invalid phrase: {}", mismatch))
+            }
+            Self::InPhrase(phrase, line, err) => f.write_fmt(format_args!(
+                "in phrase '{}' at line {}: {}",
+                phrase,
+                line + 1,
+                err
+            )),
+            Self::Ambiguous(clauses) => {
+                f.write_str("ambiguous step, matched by")?;
+                for clause in clauses {
+                    f.write_fmt(format_args!("\n  {}", describe(clause)))?;
+                }
+                Ok(())
+            }
+            Self::NoMatch(candidates) => {
+                f.write_str("no step matched")?;
+                if let Some(clause) = self.suggestion() {
+                    f.write_fmt(format_args!(", did you mean: {}?", describe(clause)))?;
+                }
+                for candidate in candidates {
+                    f.write_str("\n  ")?;
+                    if !candidate.clause.is_empty() {
+                        f.write_fmt(format_args!("{}: ", describe(candidate.clause)))?;
+                    }
+                    candidate.error.fmt(f)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 #[cfg(feature = "std")]
 impl std::error::Error for MatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn strict_policy() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("The addition.");
+        assert!(matcher.next_word("the").is_err());
+        let mut matcher = Matcher::new("The addition.");
+        matcher.next_word("The")?;
+        assert!(matcher.next_word("addition").is_err());
+        let mut matcher = Matcher::new("The\u{a0}addition");
+        matcher.next_phrase("The addition")?;
+        assert!(matcher.is_empty());
+        assert!(Matcher::new("The\u{200b}addition")
+            .next_phrase("The addition")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_policy() -> Result<(), MatchError> {
+        let mut matcher = Matcher::with_policy("The\u{200b}addition, of it. ", Policy::lenient());
+        matcher.next_phrase("the addition of")?;
+        assert_eq!(matcher.next_static()?, "it");
+        assert!(matcher.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_backticks() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("the `quoted` word");
+        matcher.next_phrase(r"the \`quoted\` word")?;
+        assert!(matcher.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn synonyms() -> Result<(), MatchError> {
+        let mut synonyms = vec![vec!["sum", "total"]].into_iter().collect::<Synonyms>();
+        synonyms.insert(["Addition", "sum"]);
+        assert!(synonyms.are_synonyms("total", "addition"));
+        assert!(!synonyms.are_synonyms("total", "product"));
+        let policy = Policy::new().with_synonyms(synonyms);
+        let mut matcher = Matcher::with_policy("the total of", policy);
+        matcher.next_phrase("the addition of")?;
+        assert!(matcher.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn next_clause() -> Result<(), MatchError> {
+        let clause = [
+            Token::QueryVar("a"),
+            Token::Static("of"),
+            Token::QueryVar("b"),
+        ];
+        let mut queries = Vec::new();
+        let mut matcher = Matcher::new("the name of the user");
+        matcher.next_clause(&clause, &mut |index, m| match clause[index] {
+            Token::QueryVar(_) => {
+                queries.push(m.next_query()?);
+                Ok(())
+            }
+            ref token => m.next_token(token),
+        })?;
+        assert!(matcher.is_empty());
+        assert_eq!(queries.pop(), Some(vec![Query::key("user")]));
+        assert_eq!(queries.pop(), Some(vec![Query::key("name")]));
+        let mut matcher = Matcher::new("the name of the user");
+        assert!(matcher
+            .next_clause(&clause[..2], &mut |index, m| match clause[index] {
+                Token::QueryVar(_) => m.next_query().map(|_| ()),
+                ref token => m.next_token(token),
+            })
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn mismatch() {
+        let mut matcher = Matcher::new("the sum of the input then the output");
+        matcher.next_phrase("the sum of").unwrap();
+        matcher.next_query().unwrap();
+        let err = matcher.next_word("henceforth").unwrap_err();
+        let mismatch = err.mismatch().unwrap();
+        assert_eq!(mismatch.offset, 21);
+        assert_eq!(mismatch.column, 22);
+        assert_eq!(
+            err.to_string(),
+            "expected 'henceforth' at column 22, found 'then'"
+        );
+        let mut matcher = Matcher::new("the sum");
+        let clause = [
+            Token::Static("the"),
+            Token::Static("sum"),
+            Token::DataVar("b", None),
+        ];
+        let err = matcher
+            .next_clause(&clause, &mut |index, m| match clause[index] {
+                Token::DataVar(..) => m.next_data::<i32>().map(|_| ()),
+                ref token => m.next_token(token),
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected 'b' at column 8, found end of line"
+        );
+    }
+
+    #[test]
+    fn next_text() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("log the message  hello,  world then exit");
+        matcher.next_phrase("log the message")?;
+        let text = matcher.next_text(Some(&Token::Static("then")))?;
+        assert_eq!(text, "hello,  world");
+        matcher.next_word("then")?;
+        assert_eq!(matcher.next_text(None)?, "exit");
+        assert!(matcher.is_empty());
+        assert!(matcher.next_text(None).is_err());
+        let mut matcher = Matcher::new("log hello \\\n    world \\\n  then exit");
+        matcher.next_word("log")?;
+        let text = matcher.next_text(Some(&Token::Static("then")))?;
+        assert_eq!(text, "hello world");
+        matcher.next_word("then")?;
+        Ok(())
+    }
+
+    #[test]
+    fn next_list() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("1, 2 and 3 are numbers");
+        let list = matcher.next_list::<i32>(Some(&Token::Static("are")))?;
+        assert_eq!(list, vec![1, 2, 3]);
+        let mut matcher = Matcher::new("either `alice, bob` or `carol`");
+        let list = matcher.next_list::<String>(None)?;
+        assert_eq!(list, vec!["alice, bob", "carol"]);
+        let mut matcher = Matcher::new("1, 2, and 3");
+        assert_eq!(matcher.next_list::<i32>(None)?, vec![1, 2, 3]);
+        let mut matcher = Matcher::new("4");
+        assert_eq!(matcher.next_list::<i32>(None)?, vec![4]);
+        let mut matcher = Matcher::new("1 and 2 or 3");
+        assert!(matcher.next_list::<i32>(None).is_err());
+        let mut matcher = Matcher::new("either 1 and 2");
+        assert!(matcher.next_list::<i32>(None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn next_alternative() -> Result<(), MatchError> {
+        let mut matcher = Matcher::with_policy("Added to it", Policy::lenient());
+        assert_eq!(matcher.next_alternative("plus|added to")?, "added to");
+        assert_eq!(matcher.next_static()?, "it");
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Account(u32);
+
+    impl<'a> MatchData<'a> for Account {
+        fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+            matcher.next_word("account")?;
+            let number = matcher.next_static()?;
+            number
+                .strip_prefix('#')
+                .and_then(|number| number.parse().ok())
+                .map(Account)
+                .ok_or(MatchError::InvalidData)
+        }
+    }
+
+    #[test]
+    fn match_data() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("account #42 is open");
+        assert_eq!(matcher.next_data::<Account>()?, Account(42));
+        assert_eq!(matcher.next_static()?, "is");
+        let mut matcher = Matcher::new("account #1 and account #2");
+        let list = matcher.next_list::<Account>(None)?;
+        assert_eq!(list, vec![Account(1), Account(2)]);
+        let mut matcher = Matcher::new("account 42");
+        assert!(matches!(
+            matcher.next_data::<Account>(),
+            Err(MatchError::InvalidData)
+        ));
+        Ok(())
+    }
+}
@@ -1,13 +1,34 @@
 //! Script parsing utilities
 
-use super::matcher::{FuncMatcher, Match, MatchError};
-use super::vm::{Callable, Func, Script};
+use super::block::{is_block, skip_block, span};
+use super::clause::{unescape, Token};
+use super::gherkin::parse_tags;
+use super::matcher::{
+    Candidate, FuncMatcher, Match, MatchContext, MatchError, MatchFunc, Matcher, Policy,
+    PUNCTUATION,
+};
+use super::vm::{Callable, Func, Metadata, Script};
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::iter;
 use core::marker::PhantomData;
 
-/// A list of FuncMatchers for a given context. Output of `mod_list!` macro
-pub type ModuleList<'a, C> = Box<[FuncMatcher<'a, C>]>;
+/// A list of FuncDefs for a given context. Output of `mod_list!` macro
+pub type ModuleList<'a, C> = Box<[FuncDef<'a, C>]>;
+
+/// A function of a module: how to match it, its clause and its priority
+pub struct FuncDef<'a, C> {
+    /// Matches a line to the function
+    pub match_func: FuncMatcher<'a, C>,
+    /// The clause of the function
+    pub clause: &'static [Token<'static>],
+    /// Functions with a higher priority are matched first
+    pub priority: i32,
+}
 
 /// A Type which represents two types H and T
 pub struct Cons<H, T> {
@@ -18,23 +39,91 @@ pub struct Cons<H, T> {
 /// A Type which represents the empty Type
 pub struct Nil;
 
+/// A `Module` which only attempts the functions whose leading static tokens match a line. The
+/// index is a trie keyed on the static words, optional groups and alternatives which lead the
+/// clause of each function. Functions whose clause does not start with a static token, such as
+/// those without a clause, are attempted for every line
+pub struct Index<'a, C> {
+    definitions: Vec<FuncDef<'a, C>>,
+    root: IndexNode,
+}
+
+#[derive(Default)]
+struct IndexNode {
+    children: BTreeMap<String, IndexNode>,
+    definitions: Vec<usize>,
+}
+
+/// A `Module` which carries the `Policy` used to match its lines. The policy replaces the one of
+/// any `Matcher` it is given, so that front-ends such as `PhraseScript` and `WithResolver` match
+/// lines with it as well
+pub struct WithPolicy<M> {
+    module: M,
+    policy: Policy,
+}
+
 /// Types which implement `ModuleType` can compile a line into a `Func` and multiple lines into a
 /// `Script`
 pub trait ModuleType<'a, C> {
     type Error;
-    fn compile_line(ctx: &mut C, string: &'a str) -> Result<Func<'a>, Self::Error>;
+    /// Compile a line into a `Func`. Implementors must override either `compile_line` or
+    /// `compile_matcher`, as each is provided through the other
+    fn compile_line(ctx: &mut C, string: &'a str) -> Result<Func<'a>, Self::Error> {
+        Self::compile_matcher(ctx, Matcher::new(string))
+    }
+    /// Compile the line of a matcher into a `Func`, honoring its `Policy`. Provided for modules
+    /// which only implement `compile_line`, which is given the rest of the line without its
+    /// policy or block
+    fn compile_matcher(ctx: &mut C, matcher: Matcher<'a>) -> Result<Func<'a>, Self::Error> {
+        Self::compile_line(ctx, matcher.rest())
+    }
+    /// Compile a script line by line. The front-matter header of the script is read into its
+    /// metadata, but a header which is not valid is compiled as steps instead. `compile_checked`
+    /// reports invalid headers
     fn compile(ctx: &mut C, string: &'a str) -> Result<Script<'a>, (usize, Self::Error)> {
-        let mut script = Vec::new();
-        for (line_num, line) in string
-            .lines()
-            .enumerate()
-            .map(|(i, s)| (i, s.trim()))
-            .filter(|(_, s)| !s.is_empty())
-        {
-            let func = Self::compile_line(ctx, line).map_err(|e| (line_num, e))?;
-            script.push(func);
-        }
-        Ok(script.into())
+        Self::compile_with(ctx, string, &Policy::default())
+    }
+    fn compile_with(
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, (usize, Self::Error)> {
+        compile_script(ctx, string, policy, Self::compile_matcher)
+    }
+
+    /// Get the functions of the module. Modules which do not list their functions are compiled
+    /// line by line with `compile_matcher` by `compile_checked`, which then cannot detect
+    /// ambiguous lines
+    fn definitions() -> Vec<FuncDef<'a, C>> {
+        Vec::new()
+    }
+
+    /// Compile a script, reporting every line which fails to compile or which is matched by more
+    /// than one function of the highest matching priority
+    fn compile_checked(ctx: &mut C, string: &'a str) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+        Self::Error: Into<MatchError>,
+    {
+        Self::compile_checked_with(ctx, string, &Policy::default())
+    }
+    fn compile_checked_with(
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+        Self::Error: Into<MatchError>,
+    {
+        let definitions = Self::definitions();
+        compile_checked(ctx, string, policy, |ctx, matcher| {
+            if definitions.is_empty() {
+                Self::compile_matcher(ctx, matcher).map_err(Into::into)
+            } else {
+                compile_matcher_checked(by_priority(&definitions), ctx, matcher)
+            }
+        })
     }
 }
 
@@ -42,56 +131,644 @@ pub trait ModuleType<'a, C> {
 /// `Script` through instance methods
 pub trait Module<'a, C> {
     type Error;
-    fn compile_line(&self, ctx: &mut C, string: &'a str) -> Result<Func<'a>, Self::Error>;
+    /// Compile a line into a `Func`. Implementors must override either `compile_line` or
+    /// `compile_matcher`, as each is provided through the other
+    fn compile_line(&self, ctx: &mut C, string: &'a str) -> Result<Func<'a>, Self::Error> {
+        self.compile_matcher(ctx, Matcher::new(string))
+    }
+    /// Compile the line of a matcher into a `Func`, honoring its `Policy`. Provided for modules
+    /// which only implement `compile_line`, which is given the rest of the line without its
+    /// policy or block
+    fn compile_matcher(&self, ctx: &mut C, matcher: Matcher<'a>) -> Result<Func<'a>, Self::Error> {
+        self.compile_line(ctx, matcher.rest())
+    }
+    /// Compile a script line by line. The front-matter header of the script is read into its
+    /// metadata, but a header which is not valid is compiled as steps instead. `compile_checked`
+    /// reports invalid headers
     fn compile(&self, ctx: &mut C, string: &'a str) -> Result<Script<'a>, (usize, Self::Error)> {
-        let mut script = Vec::new();
-        for (line_num, line) in string
-            .lines()
-            .enumerate()
-            .map(|(i, s)| (i, s.trim()))
-            .filter(|(_, s)| !s.is_empty())
-        {
-            let func = self.compile_line(ctx, line).map_err(|e| (line_num, e))?;
-            script.push(func);
+        self.compile_with(ctx, string, &Policy::default())
+    }
+    fn compile_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, (usize, Self::Error)> {
+        compile_script(ctx, string, policy, |ctx, matcher| {
+            self.compile_matcher(ctx, matcher)
+        })
+    }
+
+    /// Get the functions of the module. Modules which do not list their functions are compiled
+    /// line by line with `compile_matcher` by `compile_checked`, which then cannot detect
+    /// ambiguous lines
+    fn definitions(&self) -> &[FuncDef<'a, C>] {
+        &[]
+    }
+
+    /// Compile a script, reporting every line which fails to compile or which is matched by more
+    /// than one function of the highest matching priority
+    fn compile_checked(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+    ) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+        Self::Error: Into<MatchError>,
+    {
+        self.compile_checked_with(ctx, string, &Policy::default())
+    }
+    fn compile_checked_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+        Self::Error: Into<MatchError>,
+    {
+        let definitions = self.definitions();
+        compile_checked(ctx, string, policy, |ctx, matcher| {
+            if definitions.is_empty() {
+                self.compile_matcher(ctx, matcher).map_err(Into::into)
+            } else {
+                compile_matcher_checked(by_priority(definitions), ctx, matcher)
+            }
+        })
+    }
+}
+
+/// The functions of a list built by `mod_type!`, which are matched in place so that compiling a
+/// line does not collect their definitions
+pub trait FuncList<'a, C: MatchContext> {
+    /// Get the highest priority of the functions below a bound
+    fn priority_below(bound: i64) -> Option<i32>;
+
+    /// Compile a line with the first function of a priority to match it, collecting the functions
+    /// which fail as candidates and rolling the context back to `checkpoint` after each of them
+    fn compile_at(
+        ctx: &mut C,
+        checkpoint: &C::Checkpoint,
+        matcher: &Matcher<'a>,
+        priority: i32,
+        candidates: &mut Vec<Candidate>,
+    ) -> Option<Func<'a>>;
+}
+
+impl<'a, C> FuncDef<'a, C> {
+    /// Create the definition of a function type
+    pub fn of<T>() -> Self
+    where
+        T: MatchFunc<'a, C>,
+    {
+        Self {
+            match_func: T::match_func,
+            clause: T::clause(),
+            priority: T::priority(),
         }
-        Ok(script.into())
     }
 }
 
+impl<'a, C> Clone for FuncDef<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C> Copy for FuncDef<'a, C> {}
+
 impl<'a, H, T, C> ModuleType<'a, C> for Cons<H, T>
 where
     H: 'a + Match<'a, C> + Callable,
-    T: ModuleType<'a, C, Error = MatchError>,
+    T: ModuleType<'a, C, Error = MatchError> + FuncList<'a, C>,
     <T as ModuleType<'a, C>>::Error: Into<MatchError>,
+    C: MatchContext,
 {
     type Error = MatchError;
-    fn compile_line(ctx: &mut C, string: &'a str) -> Result<Box<dyn Callable + 'a>, Self::Error> {
-        match H::match_str(ctx, string) {
-            Ok(matched) => Ok(Box::new(matched)),
-            Err(_) => T::compile_line(ctx, string),
+    fn compile_matcher(
+        ctx: &mut C,
+        matcher: Matcher<'a>,
+    ) -> Result<Box<dyn Callable + 'a>, Self::Error> {
+        let checkpoint = ctx.checkpoint();
+        let mut candidates = Vec::new();
+        let mut priority = Self::priority_below(i64::MAX);
+        while let Some(current) = priority {
+            let func = Self::compile_at(ctx, &checkpoint, &matcher, current, &mut candidates);
+            if let Some(func) = func {
+                return Ok(func);
+            }
+            priority = Self::priority_below(current.into());
         }
+        Err(no_match(candidates))
+    }
+    fn definitions() -> Vec<FuncDef<'a, C>> {
+        let mut definitions = vec![FuncDef::of::<H>()];
+        definitions.extend(T::definitions());
+        definitions
     }
 }
 
 impl<'a, C> ModuleType<'a, C> for Nil {
     type Error = MatchError;
-    fn compile_line(_: &mut C, _: &'a str) -> Result<Box<dyn Callable + 'a>, Self::Error> {
-        Err(MatchError::UnexpectedEof)
+    fn compile_matcher(_: &mut C, _: Matcher<'a>) -> Result<Box<dyn Callable + 'a>, Self::Error> {
+        Err(MatchError::NoMatch(Vec::new()))
+    }
+}
+
+impl<'a, H, T, C> FuncList<'a, C> for Cons<H, T>
+where
+    H: 'a + Match<'a, C> + Callable,
+    T: FuncList<'a, C>,
+    C: MatchContext,
+{
+    fn priority_below(bound: i64) -> Option<i32> {
+        let priority = Some(H::priority()).filter(|priority| i64::from(*priority) < bound);
+        priority.max(T::priority_below(bound))
+    }
+    fn compile_at(
+        ctx: &mut C,
+        checkpoint: &C::Checkpoint,
+        matcher: &Matcher<'a>,
+        priority: i32,
+        candidates: &mut Vec<Candidate>,
+    ) -> Option<Func<'a>> {
+        if H::priority() == priority {
+            match H::match_func(ctx, matcher.clone()) {
+                Ok(func) => return Some(func),
+                Err(err) => {
+                    candidates.push(Candidate::new(H::clause(), err));
+                    ctx.rollback(checkpoint);
+                }
+            }
+        }
+        T::compile_at(ctx, checkpoint, matcher, priority, candidates)
+    }
+}
+
+impl<'a, C: MatchContext> FuncList<'a, C> for Nil {
+    fn priority_below(_: i64) -> Option<i32> {
+        None
+    }
+    fn compile_at(
+        _: &mut C,
+        _: &C::Checkpoint,
+        _: &Matcher<'a>,
+        _: i32,
+        _: &mut Vec<Candidate>,
+    ) -> Option<Func<'a>> {
+        None
     }
 }
 
 impl<'a, C, T> Module<'a, C> for T
 where
-    T: AsRef<[FuncMatcher<'a, C>]>,
+    T: AsRef<[FuncDef<'a, C>]>,
+    C: MatchContext,
 {
     type Error = MatchError;
-    fn compile_line(&self, ctx: &mut C, string: &'a str) -> Result<Func<'a>, Self::Error> {
-        for match_str in self.as_ref().into_iter() {
-            if let Ok(func) = match_str(ctx, string) {
-                return Ok(func);
+    fn compile_matcher(&self, ctx: &mut C, matcher: Matcher<'a>) -> Result<Func<'a>, Self::Error> {
+        compile_matcher(by_priority(self.as_ref()), ctx, matcher)
+    }
+    fn definitions(&self) -> &[FuncDef<'a, C>] {
+        self.as_ref()
+    }
+}
+
+impl<M> WithPolicy<M> {
+    /// Pair a `Module` with a `Policy`
+    pub fn new(module: M, policy: Policy) -> Self {
+        Self { module, policy }
+    }
+
+    /// Get the matching policy
+    #[inline]
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Get a mutable reference to the matching policy
+    #[inline]
+    pub fn policy_mut(&mut self) -> &mut Policy {
+        &mut self.policy
+    }
+
+    /// Get the inner `Module`
+    #[inline]
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+}
+
+impl<'a, C, M> Module<'a, C> for WithPolicy<M>
+where
+    M: Module<'a, C>,
+{
+    type Error = M::Error;
+    fn compile_matcher(
+        &self,
+        ctx: &mut C,
+        mut matcher: Matcher<'a>,
+    ) -> Result<Func<'a>, Self::Error> {
+        matcher.set_policy(self.policy.clone());
+        self.module.compile_matcher(ctx, matcher)
+    }
+    fn compile_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        _: &Policy,
+    ) -> Result<Script<'a>, (usize, Self::Error)> {
+        self.module.compile_with(ctx, string, &self.policy)
+    }
+    fn definitions(&self) -> &[FuncDef<'a, C>] {
+        self.module.definitions()
+    }
+    fn compile_checked_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        _: &Policy,
+    ) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+        Self::Error: Into<MatchError>,
+    {
+        self.module.compile_checked_with(ctx, string, &self.policy)
+    }
+}
+
+impl<'a, C> Index<'a, C> {
+    /// Index a list of functions
+    pub fn new<I>(definitions: I) -> Self
+    where
+        I: IntoIterator<Item = FuncDef<'a, C>>,
+    {
+        let mut definitions = definitions.into_iter().collect::<Vec<_>>();
+        definitions.sort_by_key(|definition| Reverse(definition.priority));
+        let mut root = IndexNode::default();
+        for (index, definition) in definitions.iter().enumerate() {
+            root.insert(definition.clause, index);
+        }
+        Self { definitions, root }
+    }
+
+    /// Get the functions which may match the line of a matcher, ordered by priority. A function
+    /// which is not returned cannot match the line as long as its clause describes the lines it
+    /// matches
+    pub fn candidates(&self, matcher: &Matcher<'a>) -> Vec<&FuncDef<'a, C>> {
+        let mut indices = Vec::new();
+        self.root.lookup(matcher.clone(), &mut indices);
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .map(|index| &self.definitions[index])
+            .collect()
+    }
+}
+
+impl IndexNode {
+    fn insert(&mut self, clause: &[Token], index: usize) {
+        match clause.split_first() {
+            Some((Token::Static(word), rest)) => self.child(word).insert(rest, index),
+            Some((Token::Optional(phrase), rest)) => {
+                self.insert(rest, index);
+                self.path(phrase).insert(rest, index);
+            }
+            Some((Token::Alternatives(alternatives), rest)) => {
+                for phrase in alternatives.split('|') {
+                    self.path(phrase).insert(rest, index);
+                }
+            }
+            _ => {
+                if !self.definitions.contains(&index) {
+                    self.definitions.push(index);
+                }
             }
         }
-        Err(MatchError::UnexpectedEof)
+    }
+
+    fn child(&mut self, word: &str) -> &mut Self {
+        self.children.entry(index_key(word)).or_default()
+    }
+
+    fn path(&mut self, phrase: &str) -> &mut Self {
+        phrase
+            .split_whitespace()
+            .fold(self, |node, word| node.child(word))
+    }
+
+    fn lookup(&self, mut matcher: Matcher, out: &mut Vec<usize>) {
+        out.extend_from_slice(&self.definitions);
+        if self.children.is_empty() {
+            return;
+        }
+        let found = match matcher.next_static() {
+            Ok(found) => found,
+            Err(_) => return,
+        };
+        let key = index_key(found);
+        if let Some(child) = self.children.get(&key) {
+            child.lookup(matcher.clone(), out);
+        }
+        let synonyms = &matcher.policy().synonyms;
+        if !synonyms.is_empty() {
+            for (word, child) in &self.children {
+                if *word != key && synonyms.are_synonyms(&key, word) {
+                    child.lookup(matcher.clone(), out);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, C: MatchContext> Module<'a, C> for Index<'a, C> {
+    type Error = MatchError;
+    fn compile_matcher(&self, ctx: &mut C, matcher: Matcher<'a>) -> Result<Func<'a>, Self::Error> {
+        compile_matcher(self.candidates(&matcher), ctx, matcher)
+    }
+    fn definitions(&self) -> &[FuncDef<'a, C>] {
+        &self.definitions
+    }
+    fn compile_checked_with(
+        &self,
+        ctx: &mut C,
+        string: &'a str,
+        policy: &Policy,
+    ) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+    where
+        C: MatchContext,
+    {
+        compile_checked(ctx, string, policy, |ctx, matcher| {
+            compile_matcher_checked(self.candidates(&matcher), ctx, matcher)
+        })
+    }
+}
+
+/// Iterate over the numbered steps of a script without their comments. A line ending in `\`
+/// continues its step on the following line, unless that line is empty or the continued line has a
+/// comment. A doc string or data table directly following a step ends it and is kept verbatim.
+/// Steps are numbered by their first line
+pub(crate) fn lines(string: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut lines = string.lines().enumerate().peekable();
+    iter::from_fn(move || loop {
+        let (line_num, line) = lines.next()?;
+        let (content, mut has_comment) = split_comment(line);
+        if content.is_empty() {
+            continue;
+        }
+        let mut last = content;
+        while let Some(&(_, next)) = lines.peek() {
+            if is_block(next) {
+                lines.next();
+                last = skip_block(next, &mut lines);
+                break;
+            }
+            let (content, next_has_comment) = split_comment(next);
+            if has_comment || content.is_empty() || !last.ends_with('\\') {
+                break;
+            }
+            last = content;
+            has_comment = next_has_comment;
+            lines.next();
+        }
+        return Some((line_num, span(string, content, last)));
+    })
+}
+
+/// Get the trimmed content of a line without its comment and whether the line has a comment
+fn split_comment(line: &str) -> (&str, bool) {
+    let stripped = strip_comment(line);
+    (stripped.trim(), stripped.len() < line.len())
+}
+
+/// Remove the comment of a line. Comments take up a whole line starting with `#`, or start with a
+/// `#` between whitespace outside of backticks
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with('#') {
+        return "";
+    }
+    let mut is_quoted = false;
+    let mut is_escaped = false;
+    let mut follows_whitespace = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            '`' => is_quoted = !is_quoted,
+            '#' if !is_quoted
+                && follows_whitespace
+                && !line[i + 1..].starts_with(|c: char| !c.is_whitespace()) =>
+            {
+                return &line[..i];
+            }
+            _ => {}
+        }
+        follows_whitespace = c.is_whitespace();
+    }
+    line
+}
+
+/// Parse the optional front-matter header of a script, which holds `key: value` lines between two
+/// `---` lines. Returns the metadata and the number of the line after the header
+pub(crate) fn parse_header(string: &str) -> Result<(Metadata<'_>, usize), (usize, MatchError)> {
+    let mut metadata = Metadata::default();
+    let mut lines = lines(string);
+    let start = match lines.next() {
+        Some((line_num, "---")) => line_num,
+        _ => return Ok((metadata, 0)),
+    };
+    for (line_num, line) in lines {
+        if line == "---" {
+            return Ok((metadata, line_num + 1));
+        }
+        parse_header_line(&mut metadata, line).map_err(|e| (line_num, e))?;
+    }
+    let mismatch = Matcher::new("").mismatch("---");
+    Err((start, MatchError::InvalidHeader(mismatch)))
+}
+
+/// Iterate over the numbered steps of a script which follow its header
+pub(crate) fn steps(string: &str, start: usize) -> impl Iterator<Item = (usize, &str)> {
+    lines(string).filter(move |(line_num, _)| *line_num >= start)
+}
+
+fn parse_header_line<'a>(metadata: &mut Metadata<'a>, line: &'a str) -> Result<(), MatchError> {
+    const KEYS: &str = "title, version, author, description or tags";
+    let invalid = || MatchError::InvalidHeader(Matcher::new(line).mismatch(KEYS));
+    let (key, value) = line.split_once(':').ok_or_else(invalid)?;
+    if key.trim().eq_ignore_ascii_case("tags") {
+        let value = value.trim_start();
+        let start = line.len() - value.len();
+        return parse_tags(value, &mut metadata.tags).map_err(|mut mismatch| {
+            mismatch.offset += start;
+            mismatch.column += line[..start].chars().count();
+            MatchError::InvalidHeader(mismatch)
+        });
+    }
+    let field = match key.trim() {
+        key if key.eq_ignore_ascii_case("title") => &mut metadata.title,
+        key if key.eq_ignore_ascii_case("version") => &mut metadata.version,
+        key if key.eq_ignore_ascii_case("author") => &mut metadata.author,
+        key if key.eq_ignore_ascii_case("description") => &mut metadata.description,
+        _ => return Err(invalid()),
+    };
+    *field = Some(value.trim());
+    Ok(())
+}
+
+/// Iterate over functions by descending priority, keeping the order of functions of equal
+/// priority. Each priority takes a pass over the functions, so that they are neither copied nor
+/// sorted for every line
+pub(crate) fn by_priority<'d, 'a, C>(
+    definitions: &'d [FuncDef<'a, C>],
+) -> impl Iterator<Item = &'d FuncDef<'a, C>> {
+    let below = move |bound: i64| {
+        definitions
+            .iter()
+            .map(|definition| definition.priority)
+            .filter(|priority| i64::from(*priority) < bound)
+            .max()
+    };
+    iter::successors(below(i64::MAX), move |priority| below((*priority).into())).flat_map(
+        move |priority| {
+            definitions
+                .iter()
+                .filter(move |definition| definition.priority == priority)
+        },
+    )
+}
+
+/// Compile a line with the first function to match it, rolling the context back after each
+/// function which fails
+fn compile_matcher<'d, 'a: 'd, C: 'd + MatchContext, I>(
+    definitions: I,
+    ctx: &mut C,
+    matcher: Matcher<'a>,
+) -> Result<Func<'a>, MatchError>
+where
+    I: IntoIterator<Item = &'d FuncDef<'a, C>>,
+{
+    let checkpoint = ctx.checkpoint();
+    let mut candidates = Vec::new();
+    for definition in definitions {
+        match (definition.match_func)(ctx, matcher.clone()) {
+            Ok(func) => return Ok(func),
+            Err(err) => {
+                candidates.push(Candidate::new(definition.clause, err));
+                ctx.rollback(&checkpoint);
+            }
+        }
+    }
+    Err(no_match(candidates))
+}
+
+/// Compile a line with every function of the highest priority which matches it, failing if there
+/// is more than one
+pub(crate) fn compile_matcher_checked<'d, 'a: 'd, C: 'd + MatchContext, I>(
+    definitions: I,
+    ctx: &mut C,
+    matcher: Matcher<'a>,
+) -> Result<Func<'a>, MatchError>
+where
+    I: IntoIterator<Item = &'d FuncDef<'a, C>>,
+{
+    let checkpoint = ctx.checkpoint();
+    let mut matched = None;
+    let mut clauses = Vec::new();
+    let mut candidates = Vec::new();
+    for definition in definitions {
+        if matches!(matched, Some((_, _, priority)) if definition.priority < priority) {
+            break;
+        }
+        match (definition.match_func)(ctx, matcher.clone()) {
+            Ok(func) => {
+                clauses.push(definition.clause);
+                if matched.is_none() {
+                    matched = Some((func, ctx.checkpoint(), definition.priority));
+                }
+            }
+            Err(err) => candidates.push(Candidate::new(definition.clause, err)),
+        }
+        ctx.rollback(&checkpoint);
+    }
+    match matched {
+        None => Err(no_match(candidates)),
+        Some(_) if clauses.len() > 1 => Err(MatchError::Ambiguous(clauses)),
+        Some((func, matched_ctx, _)) => {
+            ctx.rollback(&matched_ctx);
+            Ok(func)
+        }
+    }
+}
+
+/// Compile every line of a script with `compile_line`, stopping at the first error. The lines of
+/// an invalid header are compiled as steps
+fn compile_script<'a, C, E, F>(
+    ctx: &mut C,
+    string: &'a str,
+    policy: &Policy,
+    mut compile_line: F,
+) -> Result<Script<'a>, (usize, E)>
+where
+    F: FnMut(&mut C, Matcher<'a>) -> Result<Func<'a>, E>,
+{
+    let (metadata, start) = parse_header(string).unwrap_or_default();
+    let mut script = Vec::new();
+    for (line_num, line) in steps(string, start) {
+        let matcher = Matcher::with_policy(line, policy.clone());
+        let func = compile_line(ctx, matcher).map_err(|e| (line_num, e))?;
+        script.push(func);
+    }
+    let mut script = Script::from(script);
+    *script.metadata_mut() = metadata;
+    Ok(script)
+}
+
+/// Compile every line of a script with `compile_line`, collecting the errors of all lines
+fn compile_checked<'a, C, F>(
+    ctx: &mut C,
+    string: &'a str,
+    policy: &Policy,
+    mut compile_line: F,
+) -> Result<Script<'a>, Vec<(usize, MatchError)>>
+where
+    F: FnMut(&mut C, Matcher<'a>) -> Result<Func<'a>, MatchError>,
+{
+    let (metadata, start) = parse_header(string).map_err(|err| vec![err])?;
+    let mut script = Vec::new();
+    let mut errors = Vec::new();
+    for (line_num, line) in steps(string, start) {
+        let matcher = Matcher::with_policy(line, policy.clone());
+        match compile_line(ctx, matcher) {
+            Ok(func) => script.push(func),
+            Err(err) => errors.push((line_num, err)),
+        }
+    }
+    if errors.is_empty() {
+        let mut script = Script::from(script);
+        *script.metadata_mut() = metadata;
+        Ok(script)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Order failed candidates by how far they matched
+fn no_match(mut candidates: Vec<Candidate>) -> MatchError {
+    candidates.sort_by_key(|candidate| Reverse(candidate.offset()));
+    MatchError::NoMatch(candidates)
+}
+
+/// Normalize a static token to its key in an `Index`
+fn index_key(word: &str) -> String {
+    let word = word.trim_end_matches(PUNCTUATION);
+    if word.contains('\\') {
+        unescape(word).flat_map(char::to_lowercase).collect()
+    } else {
+        word.chars().flat_map(char::to_lowercase).collect()
     }
 }
 
@@ -129,7 +806,7 @@ macro_rules! mod_list {
         ::std::boxed::Box::new([])
     };
     ($ctx:ty => $($item:ty),*) => {
-        ::std::boxed::Box::new([$(<$item as $crate::matcher::MatchFunc<$ctx>>::match_func),*])
+        ::std::boxed::Box::new([$($crate::module::FuncDef::<$ctx>::of::<$item>()),*])
     }
 }
 #[cfg(not(feature = "std"))]
@@ -139,6 +816,6 @@ macro_rules! mod_list {
         ::alloc::boxed::Box::new([])
     };
     ($ctx:ty => $($item:ty),*) => {
-        ::alloc::boxed::Box::new([$(<$item as $crate::matcher::MatchFunc<$ctx>>::match_func),*])
+        ::alloc::boxed::Box::new([$($crate::module::FuncDef::<$ctx>::of::<$item>()),*])
     }
 }
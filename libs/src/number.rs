@@ -0,0 +1,532 @@
+//! Numbers written as numerals or English words, such as "1,000.50", "1.5e3", "12%", "twenty-one"
+//! or "the third"
+
+use crate::matcher::{MatchData, MatchError, Matcher};
+use alloc::string::String;
+use core::cell::Cell;
+use core::convert::TryFrom;
+use serde::de::value::Error;
+use serde::de::{Error as _, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+/// A number read from a `Matcher`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+/// How a word of a spelled-out number may be followed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Word {
+    Start,
+    Unit,
+    Tens,
+    Hundred,
+    Scale(u64),
+    And,
+}
+
+/// A `Deserializer` which records whether a type asks for a primitive number
+struct Probe(Cell<bool>);
+
+const UNITS: &[(&str, &str)] = &[
+    ("zero", "zeroth"),
+    ("one", "first"),
+    ("two", "second"),
+    ("three", "third"),
+    ("four", "fourth"),
+    ("five", "fifth"),
+    ("six", "sixth"),
+    ("seven", "seventh"),
+    ("eight", "eighth"),
+    ("nine", "ninth"),
+    ("ten", "tenth"),
+    ("eleven", "eleventh"),
+    ("twelve", "twelfth"),
+    ("thirteen", "thirteenth"),
+    ("fourteen", "fourteenth"),
+    ("fifteen", "fifteenth"),
+    ("sixteen", "sixteenth"),
+    ("seventeen", "seventeenth"),
+    ("eighteen", "eighteenth"),
+    ("nineteen", "nineteenth"),
+];
+
+const TENS: &[(&str, &str)] = &[
+    ("twenty", "twentieth"),
+    ("thirty", "thirtieth"),
+    ("forty", "fortieth"),
+    ("fifty", "fiftieth"),
+    ("sixty", "sixtieth"),
+    ("seventy", "seventieth"),
+    ("eighty", "eightieth"),
+    ("ninety", "ninetieth"),
+];
+
+const SCALES: &[(&str, &str, u64)] = &[
+    ("thousand", "thousandth", 1_000),
+    ("million", "millionth", 1_000_000),
+    ("billion", "billionth", 1_000_000_000),
+    ("trillion", "trillionth", 1_000_000_000_000),
+];
+
+impl Number {
+    /// Deserialize the number into `T`. Integral floats are also offered as integers
+    pub fn deserialize<'de, T>(self) -> Result<T, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        match self {
+            Self::Int(value) => T::deserialize(value.into_deserializer()),
+            Self::UInt(value) => T::deserialize(value.into_deserializer()),
+            Self::Float(value) => T::deserialize(value.into_deserializer()).or_else(|err| {
+                let int = value as i64;
+                if int as f64 == value && value > i64::MIN as f64 && value < i64::MAX as f64 {
+                    T::deserialize(int.into_deserializer())
+                } else {
+                    Err(err)
+                }
+            }),
+        }
+    }
+
+    fn from_int(value: u64, negative: bool) -> Option<Self> {
+        if !negative {
+            Some(i64::try_from(value).map_or(Self::UInt(value), Self::Int))
+        } else if value <= i64::MAX as u64 + 1 {
+            Some(Self::Int((value as i64).wrapping_neg()))
+        } else {
+            None
+        }
+    }
+
+    fn negate(self) -> Option<Self> {
+        match self {
+            Self::Int(value) => value.checked_neg().map(Self::Int),
+            Self::UInt(value) => Self::from_int(value, true),
+            Self::Float(value) => Some(Self::Float(-value)),
+        }
+    }
+
+    pub(crate) fn to_float(self) -> f64 {
+        match self {
+            Self::Int(value) => value as f64,
+            Self::UInt(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+}
+
+impl<'a> MatchData<'a> for Number {
+    fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        let mut next = matcher.clone();
+        let (mut number, is_ordinal) = next_number(&mut next)?;
+        if !is_ordinal {
+            let mut percent = next.clone();
+            if matches!(percent.next_static(), Ok(word) if word == "%" || word.eq_ignore_ascii_case("percent"))
+            {
+                number = Number::Float(number.to_float() / 100.0);
+                next = percent;
+            }
+        }
+        *matcher = next;
+        Ok(number)
+    }
+}
+
+/// Check whether `T` deserializes from a primitive number such as `u32` or `f64`. Only such types
+/// are read as a `Number` in place of NLSD
+pub(crate) fn is_primitive<'de, T>() -> bool
+where
+    T: Deserialize<'de>,
+{
+    let probe = Probe(Cell::new(false));
+    let _ = T::deserialize(&probe);
+    probe.0.get()
+}
+
+/// Read the next cardinal number without a following percent sign, such as the value of a
+/// quantity
+pub(crate) fn next_cardinal(matcher: &mut Matcher) -> Result<Number, MatchError> {
+    let mut next = matcher.clone();
+    match next_number(&mut next)? {
+        (number, false) => {
+            *matcher = next;
+            Ok(number)
+        }
+        _ => Err(MatchError::InvalidData),
+    }
+}
+
+/// Read the next number, which may be negated by "minus" or be an ordinal following "the".
+/// Ordinals, whether numerals such as "21st" or words such as "twenty-first", are only read after
+/// "the". Returns the number and whether it is an ordinal
+fn next_number(matcher: &mut Matcher) -> Result<(Number, bool), MatchError> {
+    let mut next = matcher.clone();
+    let word = next.next_static()?;
+    let found = if word.eq_ignore_ascii_case("the") {
+        match next_value(&mut next, true)? {
+            (number, true) => (number, true),
+            _ => return Err(MatchError::InvalidData),
+        }
+    } else if word.eq_ignore_ascii_case("minus") || word.eq_ignore_ascii_case("negative") {
+        match next_value(&mut next, false)? {
+            (number, false) => (number.negate().ok_or(MatchError::InvalidData)?, false),
+            _ => return Err(MatchError::InvalidData),
+        }
+    } else {
+        next = matcher.clone();
+        match next_value(&mut next, false)? {
+            (number, false) => (number, false),
+            _ => return Err(MatchError::InvalidData),
+        }
+    };
+    *matcher = next;
+    Ok(found)
+}
+
+macro_rules! probe_numbers {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+                self.0.set(true);
+                Err(Error::custom("probed a number"))
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for &Probe {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+        Err(Error::custom("probed another type"))
+    }
+
+    probe_numbers! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+    }
+
+    forward_to_deserialize_any! {
+        bool char str string bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// The state of a number spelled out in words
+#[derive(Clone, Copy, Debug)]
+struct Words {
+    total: u64,
+    current: u64,
+    last: Word,
+    smallest_scale: u64,
+}
+
+impl Words {
+    fn new() -> Self {
+        Self {
+            total: 0,
+            current: 0,
+            last: Word::Start,
+            smallest_scale: u64::MAX,
+        }
+    }
+
+    fn value(&self) -> Option<u64> {
+        self.total.checked_add(self.current)
+    }
+
+    /// Add a possibly hyphenated word. Returns whether the word is an ordinal. Ordinals are only
+    /// accepted with `allow_ordinal` so that "thirty second" reads as thirty
+    fn push_word(&mut self, word: &str, allow_ordinal: bool) -> Option<bool> {
+        let mut parts = word.split('-').peekable();
+        while let Some(part) = parts.next() {
+            let (kind, value, is_ordinal) = classify(part)?;
+            if is_ordinal && (parts.peek().is_some() || !allow_ordinal) {
+                return None;
+            }
+            self.push(kind, value)?;
+            if is_ordinal {
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+
+    fn push(&mut self, kind: Word, value: u64) -> Option<()> {
+        let is_valid = match (self.last, kind) {
+            (Word::Start, Word::Unit) | (Word::Start, Word::Tens) => true,
+            (Word::Tens, Word::Unit) => (1..10).contains(&value),
+            (Word::Hundred, Word::Unit)
+            | (Word::Scale(_), Word::Unit)
+            | (Word::And, Word::Unit) => value > 0,
+            (Word::Hundred, Word::Tens)
+            | (Word::Scale(_), Word::Tens)
+            | (Word::And, Word::Tens) => true,
+            (Word::Unit, Word::Hundred) => (1..100).contains(&self.current),
+            (Word::Unit, Word::Scale(scale))
+            | (Word::Tens, Word::Scale(scale))
+            | (Word::Hundred, Word::Scale(scale)) => {
+                self.current > 0 && scale < self.smallest_scale
+            }
+            _ => false,
+        };
+        if !is_valid {
+            return None;
+        }
+        match kind {
+            Word::Unit | Word::Tens => self.current += value,
+            Word::Hundred => self.current *= 100,
+            Word::Scale(scale) => {
+                self.total = self.total.checked_add(self.current.checked_mul(scale)?)?;
+                self.current = 0;
+                self.smallest_scale = scale;
+            }
+            Word::Start | Word::And => {}
+        }
+        self.last = kind;
+        Some(())
+    }
+
+    /// Add a word which may join number words, such as "and" in "one hundred and five" or "a" in
+    /// "a thousand". "and" is only read with `allow_and`
+    fn connect(&mut self, word: &str, allow_and: bool) -> bool {
+        match (self.last, word) {
+            (Word::Hundred, "and") | (Word::Scale(_), "and") if allow_and => {
+                self.last = Word::And;
+                true
+            }
+            (Word::Start, "a") | (Word::Start, "an") => {
+                self.current = 1;
+                self.last = Word::Unit;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Read the next numeral or number spelled out in words. Returns the number and whether it is an
+/// ordinal. Ordinal words are only read with `allow_ordinal`
+fn next_value(matcher: &mut Matcher, allow_ordinal: bool) -> Result<(Number, bool), MatchError> {
+    let mut next = matcher.clone();
+    if let Some(found) = numeral(next.next_static()?) {
+        *matcher = next;
+        return Ok(found);
+    }
+    let (value, is_ordinal) = next_words(matcher, allow_ordinal).ok_or(MatchError::InvalidData)?;
+    let number = Number::from_int(value, false).ok_or(MatchError::InvalidData)?;
+    Ok((number, is_ordinal))
+}
+
+/// Parse a numeral with optional grouping separators, fraction, exponent, ordinal suffix or
+/// percent sign. Returns the number and whether it is an ordinal
+pub(crate) fn numeral(word: &str) -> Option<(Number, bool)> {
+    let (word, percent) = match word.strip_suffix('%') {
+        Some(word) => (word, true),
+        None => (word, false),
+    };
+    let (sign, unsigned) = match word.strip_prefix(['-', '+']) {
+        Some(unsigned) => (&word[..1], unsigned),
+        None => ("", word),
+    };
+    let end = unsigned
+        .find(|c: char| !(c.is_ascii_digit() || c == ','))
+        .unwrap_or(unsigned.len());
+    let (int, rest) = unsigned.split_at(end);
+    let mut groups = int.split(',');
+    let first = groups.next()?;
+    if first.is_empty() || (int.contains(',') && first.len() > 3) {
+        return None;
+    }
+    if !groups.all(|group| group.len() == 3) {
+        return None;
+    }
+    let digits: String = int.chars().filter(|c| *c != ',').collect();
+    let suffix = rest.to_ascii_lowercase();
+    if ["st", "nd", "rd", "th"].contains(&suffix.as_str()) {
+        return if sign.is_empty() && !percent {
+            Some((Number::from_int(digits.parse().ok()?, false)?, true))
+        } else {
+            None
+        };
+    }
+    let (fraction, exponent) = match rest.find(['e', 'E']) {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, ""),
+    };
+    let valid_fraction = fraction.is_empty()
+        || (fraction.len() > 1
+            && fraction.starts_with('.')
+            && fraction[1..].bytes().all(|b| b.is_ascii_digit()));
+    let valid_exponent = exponent.is_empty() || {
+        let exponent = exponent[1..].trim_start_matches(['-', '+']);
+        !exponent.is_empty() && exponent.bytes().all(|b| b.is_ascii_digit())
+    };
+    if !valid_fraction || !valid_exponent {
+        return None;
+    }
+    let number = if fraction.is_empty() && exponent.is_empty() && !percent {
+        Number::from_int(digits.parse().ok()?, sign == "-")?
+    } else {
+        let mut float = String::from(sign);
+        float.push_str(&digits);
+        float.push_str(fraction);
+        float.push_str(exponent);
+        let value: f64 = float.parse().ok()?;
+        Number::Float(if percent { value / 100.0 } else { value })
+    };
+    Some((number, false))
+}
+
+/// Read a number spelled out in English words such as "one hundred and twenty-first". Returns the
+/// value and whether it is an ordinal. A number followed by "and" in its clause stops before "and"
+fn next_words(matcher: &mut Matcher, allow_ordinal: bool) -> Option<(u64, bool)> {
+    let allow_and = !matcher.is_before_and();
+    let mut words = Words::new();
+    loop {
+        let mut next = matcher.clone();
+        let word = match next.next_static() {
+            Ok(word) => word.to_ascii_lowercase(),
+            Err(_) => break,
+        };
+        let mut state = words;
+        let is_ordinal = if state.connect(&word, allow_and) {
+            next.next_static()
+                .ok()
+                .and_then(|word| state.push_word(&word.to_ascii_lowercase(), allow_ordinal))
+        } else {
+            state.push_word(&word, allow_ordinal)
+        };
+        match is_ordinal {
+            Some(is_ordinal) => {
+                words = state;
+                *matcher = next;
+                if is_ordinal {
+                    return Some((words.value()?, true));
+                }
+            }
+            None => break,
+        }
+    }
+    match words.last {
+        Word::Start => None,
+        _ => Some((words.value()?, false)),
+    }
+}
+
+/// Classify a lower case number word, returning its kind, value and whether it is an ordinal
+fn classify(word: &str) -> Option<(Word, u64, bool)> {
+    for (value, (cardinal, ordinal)) in UNITS.iter().enumerate() {
+        if word == *cardinal || word == *ordinal {
+            return Some((Word::Unit, value as u64, word == *ordinal));
+        }
+    }
+    for (index, (cardinal, ordinal)) in TENS.iter().enumerate() {
+        if word == *cardinal || word == *ordinal {
+            return Some((Word::Tens, (index as u64 + 2) * 10, word == *ordinal));
+        }
+    }
+    if word == "hundred" || word == "hundredth" {
+        return Some((Word::Hundred, 100, word == "hundredth"));
+    }
+    SCALES
+        .iter()
+        .find(|(cardinal, ordinal, _)| word == *cardinal || word == *ordinal)
+        .map(|(_, ordinal, scale)| (Word::Scale(*scale), *scale, word == *ordinal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(src: &str) -> Result<Number, MatchError> {
+        let mut matcher = Matcher::new(src);
+        let number = matcher.next_data()?;
+        assert!(matcher.is_empty(), "{}", src);
+        Ok(number)
+    }
+
+    #[test]
+    fn numerals() -> Result<(), MatchError> {
+        assert_eq!(number("42")?, Number::Int(42));
+        assert_eq!(number("-7")?, Number::Int(-7));
+        assert_eq!(number("1,000")?, Number::Int(1000));
+        assert_eq!(number("1,000.50")?, Number::Float(1000.5));
+        assert_eq!(number("1.5e3")?, Number::Float(1500.0));
+        assert_eq!(number("2E-2")?, Number::Float(0.02));
+        assert_eq!(number("12%")?, Number::Float(0.12));
+        assert_eq!(number("50 percent")?, Number::Float(0.5));
+        assert_eq!(number("the 21st")?, Number::Int(21));
+        assert_eq!(number("18446744073709551615")?, Number::UInt(u64::MAX));
+        for src in ["1,00", "1000,000", "1.", "1e", "abc", "-3rd", "21st"] {
+            assert!(number(src).is_err(), "{}", src);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn words() -> Result<(), MatchError> {
+        assert_eq!(number("five")?, Number::Int(5));
+        assert_eq!(number("twenty-one")?, Number::Int(21));
+        assert_eq!(number("Twenty one")?, Number::Int(21));
+        assert_eq!(number("a hundred")?, Number::Int(100));
+        assert_eq!(number("one hundred and five")?, Number::Int(105));
+        assert_eq!(
+            number("two thousand three hundred forty-five")?,
+            Number::Int(2345)
+        );
+        assert_eq!(number("nineteen hundred")?, Number::Int(1900));
+        assert_eq!(number("minus three")?, Number::Int(-3));
+        assert_eq!(number("the third")?, Number::Int(3));
+        assert_eq!(number("the twenty-first")?, Number::Int(21));
+        assert_eq!(number("the 2nd")?, Number::Int(2));
+        assert_eq!(number("ten percent")?, Number::Float(0.1));
+        assert!(number("the five").is_err());
+        assert!(number("thousand").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn stops_at_other_words() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("five second delay");
+        assert_eq!(matcher.next_data::<Number>()?, Number::Int(5));
+        assert_eq!(matcher.next_static()?, "second");
+        let mut matcher = Matcher::new("one hundred and the rest");
+        assert_eq!(matcher.next_data::<Number>()?, Number::Int(100));
+        assert_eq!(matcher.next_static()?, "and");
+        let mut matcher = Matcher::new("a dog");
+        assert!(matcher.next_data::<Number>().is_err());
+        let mut matcher = Matcher::new("thirty second timeout");
+        assert_eq!(matcher.next_data::<Number>()?, Number::Int(30));
+        assert_eq!(matcher.next_static()?, "second");
+        let mut matcher = Matcher::new("the thirty second timeout");
+        assert_eq!(matcher.next_data::<Number>()?, Number::Int(32));
+        assert_eq!(matcher.next_static()?, "timeout");
+        assert_eq!(number("the twenty-second")?, Number::Int(22));
+        assert!(number("twenty-second").is_err());
+        assert!(number("second").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("twenty-one and 1.5e3 and 2.5 and `fifteen`");
+        assert_eq!(matcher.next_data::<i32>()?, 21);
+        matcher.next_word("and")?;
+        assert_eq!(matcher.next_data::<u16>()?, 1500);
+        matcher.next_word("and")?;
+        assert_eq!(matcher.next_data::<f32>()?, 2.5);
+        matcher.next_word("and")?;
+        assert_eq!(matcher.next_data::<String>()?, "fifteen");
+        assert!(is_primitive::<u8>());
+        assert!(is_primitive::<f64>());
+        assert!(!is_primitive::<String>());
+        assert!(!is_primitive::<Option<i32>>());
+        Ok(())
+    }
+}
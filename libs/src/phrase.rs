@@ -0,0 +1,471 @@
+//! Phrases defined by a script, such as "To settle q`account`:" followed by the indented steps which
+//! the phrase stands for. The body of a phrase holds every following step indented deeper than its
+//! definition, even across blank lines and comments
+
+use crate::clause::{describe, Parser, Token};
+use crate::matcher::{MatchError, Matcher, Mismatch, Policy};
+use crate::module::{parse_header, steps, Module, ModuleType};
+use crate::vm::{Func, Metadata, Script};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter;
+
+/// A phrase defined by a script
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Phrase<'a> {
+    /// The clause of the phrase, such as "settle q`account`"
+    pub clause: Vec<Token<'a>>,
+    /// The number of the line which defines the phrase
+    pub line: usize,
+    /// The numbered steps of the body of the phrase. The variables of the clause are written as
+    /// `<placeholders>`, such as "Add 2 to <account>"
+    pub steps: Vec<(usize, &'a str)>,
+}
+
+/// A step of a script with the phrases it was expanded from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Step<'a> {
+    /// The number of the line of the script which holds the step or which uses the phrase it was
+    /// expanded from
+    pub line: usize,
+    /// The step with the placeholders of its phrases filled in
+    pub text: Cow<'a, str>,
+    /// The description of each phrase the step was expanded from, outermost first, with the
+    /// number of the line of the step in its body
+    pub phrases: Vec<(String, usize)>,
+}
+
+/// A script whose steps may use the phrases it defines. Each use of a phrase is expanded into the
+/// steps of its body with the placeholders filled in by the text given to its variables. A
+/// placeholder left in a step once expanded is an invalid phrase
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PhraseScript<'a> {
+    /// The metadata of the script header
+    pub metadata: Metadata<'a>,
+    /// The phrases defined by the script, in order
+    pub phrases: Vec<Phrase<'a>>,
+    /// The steps of the script with the phrases they use expanded
+    pub steps: Vec<Step<'a>>,
+}
+
+/// The keyword which starts the definition of a phrase
+const TO: &str = "To ";
+
+impl<'a> PhraseScript<'a> {
+    /// Parse a script and expand the phrases it uses. Returns the number of the offending line on
+    /// failure
+    pub fn parse(string: &'a str) -> Result<Self, (usize, MatchError)> {
+        Self::parse_with(string, &Policy::default())
+    }
+
+    /// Parse a script and expand the phrases it uses, matching them with a policy
+    pub fn parse_with(string: &'a str, policy: &Policy) -> Result<Self, (usize, MatchError)> {
+        let (metadata, start) = parse_header(string)?;
+        let mut script = Self {
+            metadata,
+            ..Self::default()
+        };
+        let mut steps = steps(string, start).peekable();
+        while let Some((line_num, step)) = steps.next() {
+            if is_definition(step) {
+                let depth = indentation(string, step);
+                let mut body = Vec::new();
+                while let Some(body_step) =
+                    steps.next_if(|(_, next)| indentation(string, next) > depth)
+                {
+                    body.push(body_step);
+                }
+                let phrase = Phrase::parse(line_num, step, body)?;
+                script.phrases.push(phrase);
+                continue;
+            }
+            let step = Step {
+                line: line_num,
+                text: Cow::Borrowed(step),
+                phrases: Vec::new(),
+            };
+            expand(&script.phrases, step, policy, &mut script.steps).map_err(|e| (line_num, e))?;
+        }
+        Ok(script)
+    }
+
+    /// Compile the steps of the script with a module
+    pub fn compile<'s, M, C>(
+        &'s self,
+        module: &M,
+        ctx: &mut C,
+    ) -> Result<Script<'s>, (usize, MatchError)>
+    where
+        M: Module<'s, C>,
+        M::Error: Into<MatchError>,
+    {
+        self.compile_with(module, ctx, &Policy::default())
+    }
+
+    /// Compile the steps of the script with a module and a policy
+    pub fn compile_with<'s, M, C>(
+        &'s self,
+        module: &M,
+        ctx: &mut C,
+        policy: &Policy,
+    ) -> Result<Script<'s>, (usize, MatchError)>
+    where
+        M: Module<'s, C>,
+        M::Error: Into<MatchError>,
+    {
+        self.compile_steps(policy, |matcher| {
+            module.compile_matcher(ctx, matcher).map_err(Into::into)
+        })
+    }
+
+    /// Compile the steps of the script with a module type
+    pub fn compile_type<'s, M, C>(&'s self, ctx: &mut C) -> Result<Script<'s>, (usize, MatchError)>
+    where
+        M: ModuleType<'s, C>,
+        M::Error: Into<MatchError>,
+    {
+        self.compile_type_with::<M, C>(ctx, &Policy::default())
+    }
+
+    /// Compile the steps of the script with a module type and a policy
+    pub fn compile_type_with<'s, M, C>(
+        &'s self,
+        ctx: &mut C,
+        policy: &Policy,
+    ) -> Result<Script<'s>, (usize, MatchError)>
+    where
+        M: ModuleType<'s, C>,
+        M::Error: Into<MatchError>,
+    {
+        self.compile_steps(policy, |matcher| {
+            M::compile_matcher(ctx, matcher).map_err(Into::into)
+        })
+    }
+
+    fn compile_steps<'s, F>(
+        &'s self,
+        policy: &Policy,
+        mut compile: F,
+    ) -> Result<Script<'s>, (usize, MatchError)>
+    where
+        F: FnMut(Matcher<'s>) -> Result<Func<'s>, MatchError>,
+    {
+        let mut funcs = Vec::new();
+        for step in &self.steps {
+            let matcher = Matcher::with_policy(&step.text, policy.clone());
+            funcs.push(compile(matcher).map_err(|e| step.locate(e))?);
+        }
+        let mut script = Script::from(funcs);
+        *script.metadata_mut() = self.metadata.clone();
+        Ok(script)
+    }
+}
+
+impl<'a> Phrase<'a> {
+    /// Parse the definition of a phrase and the numbered steps of its body. A variable of the
+    /// clause must be followed by a static word or alternatives, as its text is read up to them,
+    /// and the body may only use the placeholders of its variables. Returns the number of the
+    /// offending line on failure
+    fn parse(
+        line_num: usize,
+        step: &'a str,
+        steps: Vec<(usize, &'a str)>,
+    ) -> Result<Self, (usize, MatchError)> {
+        let src = &step[TO.len()..step.len() - 1];
+        let mut parser = Parser::new(src);
+        let tokens = iter::from_fn(|| parser.next_spanned())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                (
+                    line_num,
+                    invalid(step, TO.len() + err.span().start, "clause"),
+                )
+            })?;
+        if tokens.is_empty() {
+            return Err((line_num, invalid(step, TO.len(), "clause")));
+        }
+        for ((token, _), (next, span)) in tokens.iter().zip(tokens.iter().skip(1)) {
+            if token.var_name().is_some() && !(next.is_static() || next.is_alternatives()) {
+                let offset = TO.len() + span.start;
+                return Err((line_num, invalid(step, offset, "a static word")));
+            }
+        }
+        if steps.is_empty() {
+            return Err((line_num, invalid(step, step.len(), "indented steps")));
+        }
+        let clause = tokens
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect::<Vec<_>>();
+        for &(body_line, body_step) in &steps {
+            let unknown = placeholders(body_step)
+                .find(|(_, name)| !clause.iter().any(|token| token.var_name() == Some(name)));
+            if let Some((offset, _)) = unknown {
+                return Err((
+                    body_line,
+                    invalid(body_step, offset, "a variable of the phrase"),
+                ));
+            }
+        }
+        Ok(Self {
+            clause,
+            line: line_num,
+            steps,
+        })
+    }
+
+    /// Match a step against the clause of the phrase, whose first word the step may capitalize.
+    /// Returns the text given to each variable
+    fn match_step(&self, step: &str, policy: &Policy) -> Option<Vec<(&'a str, String)>> {
+        let mut matcher = Matcher::with_policy(step, policy.clone());
+        let mut args = vec![None; self.clause.len()];
+        matcher
+            .next_clause(&self.clause, &mut |index, matcher| {
+                let token = &self.clause[index];
+                if let (0, Token::Static(word)) = (index, token) {
+                    return next_capitalized(matcher, word);
+                }
+                if token.var_name().is_none() {
+                    return matcher.next_token(token);
+                }
+                let text = matcher.next_text(self.clause.get(index + 1))?;
+                args[index] = Some(text.into_owned());
+                Ok(())
+            })
+            .ok()?;
+        Some(
+            self.clause
+                .iter()
+                .zip(args)
+                .filter_map(|(token, arg)| Some((token.var_name()?, arg?)))
+                .collect(),
+        )
+    }
+}
+
+impl<'a> Step<'a> {
+    /// Locate an error of the step at the line of the script which holds it
+    fn locate(&self, err: MatchError) -> (usize, MatchError) {
+        let err = self
+            .phrases
+            .iter()
+            .rev()
+            .fold(err, |err, (phrase, line_num)| {
+                MatchError::InPhrase(phrase.clone(), *line_num, Box::new(err))
+            });
+        (self.line, err)
+    }
+}
+
+/// Push a step, or the steps of the body of the latest phrase it uses, expanded in turn with the
+/// phrases defined before that one
+fn expand<'a>(
+    phrases: &[Phrase<'a>],
+    step: Step<'a>,
+    policy: &Policy,
+    out: &mut Vec<Step<'a>>,
+) -> Result<(), MatchError> {
+    let used = phrases
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, phrase)| Some((index, phrase, phrase.match_step(&step.text, policy)?)));
+    let (index, phrase, args) = match used {
+        Some(used) => used,
+        None => {
+            if let Some(offset) = placeholder(&step.text) {
+                return Err(step.locate(invalid(&step.text, offset, "a value")).1);
+            }
+            out.push(step);
+            return Ok(());
+        }
+    };
+    if Matcher::new(&step.text).next_block().is_some() {
+        return Err(MatchError::UnexpectedBlock);
+    }
+    let description = describe(&phrase.clause);
+    for (line_num, body_step) in &phrase.steps {
+        let mut trace = step.phrases.clone();
+        trace.push((description.clone(), *line_num));
+        let body_step = Step {
+            line: step.line,
+            text: fill(body_step, &args),
+            phrases: trace,
+        };
+        expand(&phrases[..index], body_step, policy, out)?;
+    }
+    Ok(())
+}
+
+/// Check if a step defines a phrase
+fn is_definition(step: &str) -> bool {
+    step.starts_with(TO) && step.ends_with(':')
+}
+
+/// Count the whitespace before a step on its line of the script
+fn indentation(string: &str, step: &str) -> usize {
+    let offset = step.as_ptr() as usize - string.as_ptr() as usize;
+    let line_start = string[..offset].rfind('\n').map_or(0, |i| i + 1);
+    offset - line_start
+}
+
+/// Match the next static token against a word which may be capitalized
+fn next_capitalized(matcher: &mut Matcher, word: &str) -> Result<(), MatchError> {
+    let start = matcher.clone();
+    let found = matcher.next_static()?;
+    let mut chars = found.chars();
+    let uncapitalized = chars
+        .next()
+        .map(|c| c.to_lowercase().chain(chars).collect::<String>())
+        .unwrap_or_default();
+    let policy = matcher.policy();
+    if policy.token_eq(found, word) || policy.token_eq(&uncapitalized, word) {
+        Ok(())
+    } else {
+        Err(MatchError::MismatchedStaticToken(start.mismatch(word)))
+    }
+}
+
+/// Replace the `<placeholders>` of a step with the text given to the variables of its phrase.
+/// Text in backticks is left as it is written
+fn fill<'a>(step: &'a str, args: &[(&str, String)]) -> Cow<'a, str> {
+    let mut filled = String::new();
+    let mut start = 0;
+    for (offset, name) in placeholders(step) {
+        if let Some((_, value)) = args.iter().find(|(arg, _)| *arg == name) {
+            filled.push_str(&step[start..offset]);
+            filled.push_str(value);
+            start = offset + name.len() + 2;
+        }
+    }
+    if start == 0 {
+        return Cow::Borrowed(step);
+    }
+    filled.push_str(&step[start..]);
+    Cow::Owned(filled)
+}
+
+/// Find the offset of the first `<placeholder>` of a step outside of backticks
+fn placeholder(step: &str) -> Option<usize> {
+    placeholders(step).next().map(|(offset, _)| offset)
+}
+
+/// Iterate over the offsets and names of the `<placeholders>` of a step outside of backticks
+fn placeholders(step: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut is_quoted = false;
+    let mut is_escaped = false;
+    step.char_indices().filter_map(move |(i, c)| {
+        match c {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            '`' => is_quoted = !is_quoted,
+            '<' if !is_quoted => {
+                let name = step[i + 1..].split('>').next().unwrap_or_default();
+                if !name.is_empty()
+                    && name.len() < step.len() - i - 1
+                    && !name.contains(|c: char| c.is_whitespace() || c == '<')
+                {
+                    return Some((i, name));
+                }
+            }
+            _ => {}
+        }
+        None
+    })
+}
+
+/// Describe an invalid phrase at an offset of the first line of a step
+fn invalid(line: &str, offset: usize, expected: &str) -> MatchError {
+    let offset = offset.min(line.len());
+    let found = line[offset..].split_whitespace().next().unwrap_or_default();
+    MatchError::InvalidPhrase(Mismatch {
+        offset,
+        line: 0,
+        column: line[..offset].chars().count() + 1,
+        expected: expected.into(),
+        found: found.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn expand_phrases() -> Result<(), (usize, MatchError)> {
+        let script = PhraseScript::parse(
+            r#"
+            To pay d`amount` into q`account`:
+                Add <amount> to <account>
+                Log the payment of <amount>
+            To settle q`account`:
+                Pay 5 into <account>
+                Close <account>
+
+            Settle the savings account
+            Pay 1 into the current account
+            "#,
+        )?;
+        assert_eq!(script.phrases.len(), 2);
+        assert_eq!(script.phrases[1].line, 4);
+        let steps = script
+            .steps
+            .iter()
+            .map(|step| (step.line, step.text.as_ref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            [
+                (8, "Add 5 to the savings account"),
+                (8, "Log the payment of 5"),
+                (8, "Close the savings account"),
+                (9, "Add 1 to the current account"),
+                (9, "Log the payment of 1"),
+            ]
+        );
+        assert_eq!(
+            script.steps[1].phrases,
+            [("settle …".to_string(), 5), ("pay … into …".to_string(), 3)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_phrases() {
+        for (src, message) in &[
+            (
+                "To settle q`account:\n  Close <account>",
+                "invalid phrase: expected 'clause' at column 11, found 'q`account:'",
+            ),
+            (
+                "To settle q`account`:",
+                "invalid phrase: expected 'indented steps' at column 22, found end of line",
+            ),
+            (
+                "To log t`message` [please] henceforth q`out`:\n  Log <message>",
+                "invalid phrase: expected 'a static word' at column 19, found '[please]'",
+            ),
+        ] {
+            let (line_num, err) = PhraseScript::parse(src).unwrap_err();
+            assert_eq!(line_num, 0);
+            assert_eq!(err.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn quoted_placeholders() -> Result<(), (usize, MatchError)> {
+        let script = PhraseScript::parse(
+            r#"
+            To greet q`name`:
+                Say `hello <name>` to <name>
+            Greet the guest
+            "#,
+        )?;
+        assert_eq!(script.steps[0].text, "Say `hello <name>` to the guest");
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+//! Quantities with units such as "3 kilograms", "250 ms" or "10 %"
+
+use crate::matcher::{MatchData, MatchError, Matcher};
+use crate::number::{next_cardinal, numeral};
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+
+/// The dimension of a unit. Only units of the same dimension can be converted into each other
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Dimension {
+    /// Measured in grams
+    Mass,
+    /// Measured in metres
+    Length,
+    /// Measured in seconds
+    Time,
+    /// Measured in fractions of a whole
+    Ratio,
+}
+
+/// A unit of measurement
+#[derive(Debug, PartialEq)]
+pub struct Unit {
+    /// The symbol of the unit, such as "kg"
+    pub symbol: &'static str,
+    /// The singular and plural names of the unit, such as "kilogram" and "kilograms"
+    pub names: &'static [&'static str],
+    /// The dimension of the unit
+    pub dimension: Dimension,
+    /// How many base units of the dimension make up the unit
+    pub factor: f64,
+}
+
+/// A dimension which a `Quantity` is measured in
+pub trait Measure {
+    const DIMENSION: Dimension;
+}
+
+/// Measured in grams
+#[derive(Clone, Copy, Debug)]
+pub struct Mass;
+
+/// Measured in metres
+#[derive(Clone, Copy, Debug)]
+pub struct Length;
+
+/// Measured in seconds
+#[derive(Clone, Copy, Debug)]
+pub struct Time;
+
+/// Measured in fractions of a whole
+#[derive(Clone, Copy, Debug)]
+pub struct Ratio;
+
+impl Measure for Mass {
+    const DIMENSION: Dimension = Dimension::Mass;
+}
+
+impl Measure for Length {
+    const DIMENSION: Dimension = Dimension::Length;
+}
+
+impl Measure for Time {
+    const DIMENSION: Dimension = Dimension::Time;
+}
+
+impl Measure for Ratio {
+    const DIMENSION: Dimension = Dimension::Ratio;
+}
+
+/// A value with a unit of the dimension `D`, such as "3 kilograms". Quantities compare by value
+/// regardless of unit
+#[derive(Clone, Copy, Debug)]
+pub struct Quantity<D> {
+    value: f64,
+    unit: &'static Unit,
+    dimension: PhantomData<D>,
+}
+
+impl Dimension {
+    /// Get the name of the dimension
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mass => "mass",
+            Self::Length => "length",
+            Self::Time => "time",
+            Self::Ratio => "ratio",
+        }
+    }
+}
+
+impl Unit {
+    /// One thousandth of a gram
+    pub const MILLIGRAM: Unit =
+        Unit::new("mg", &["milligram", "milligrams"], Dimension::Mass, 1e-3);
+    /// The base unit of mass
+    pub const GRAM: Unit = Unit::new("g", &["gram", "grams"], Dimension::Mass, 1.0);
+    /// One thousand grams
+    pub const KILOGRAM: Unit = Unit::new("kg", &["kilogram", "kilograms"], Dimension::Mass, 1e3);
+    /// One million grams
+    pub const TONNE: Unit = Unit::new("t", &["tonne", "tonnes"], Dimension::Mass, 1e6);
+    /// The avoirdupois ounce of 28.349523125 grams
+    pub const OUNCE: Unit = Unit::new("oz", &["ounce", "ounces"], Dimension::Mass, 28.349523125);
+    /// The avoirdupois pound of 453.59237 grams
+    pub const POUND: Unit = Unit::new("lb", &["pound", "pounds"], Dimension::Mass, 453.59237);
+    /// One thousandth of a metre
+    pub const MILLIMETRE: Unit = Unit::new(
+        "mm",
+        &["millimetre", "millimetres", "millimeter", "millimeters"],
+        Dimension::Length,
+        1e-3,
+    );
+    /// One hundredth of a metre
+    pub const CENTIMETRE: Unit = Unit::new(
+        "cm",
+        &["centimetre", "centimetres", "centimeter", "centimeters"],
+        Dimension::Length,
+        1e-2,
+    );
+    /// The base unit of length
+    pub const METRE: Unit = Unit::new(
+        "m",
+        &["metre", "metres", "meter", "meters"],
+        Dimension::Length,
+        1.0,
+    );
+    /// One thousand metres
+    pub const KILOMETRE: Unit = Unit::new(
+        "km",
+        &["kilometre", "kilometres", "kilometer", "kilometers"],
+        Dimension::Length,
+        1e3,
+    );
+    /// The international inch of 2.54 centimetres
+    pub const INCH: Unit = Unit::new("in", &["inch", "inches"], Dimension::Length, 0.0254);
+    /// The international foot of 12 inches
+    pub const FOOT: Unit = Unit::new("ft", &["foot", "feet"], Dimension::Length, 0.3048);
+    /// The international mile of 1609.344 metres
+    pub const MILE: Unit = Unit::new("mi", &["mile", "miles"], Dimension::Length, 1609.344);
+    /// One thousandth of a second
+    pub const MILLISECOND: Unit = Unit::new(
+        "ms",
+        &["millisecond", "milliseconds"],
+        Dimension::Time,
+        1e-3,
+    );
+    /// The base unit of time
+    pub const SECOND: Unit = Unit::new("s", &["second", "seconds"], Dimension::Time, 1.0);
+    /// Sixty seconds
+    pub const MINUTE: Unit = Unit::new("min", &["minute", "minutes"], Dimension::Time, 60.0);
+    /// Sixty minutes
+    pub const HOUR: Unit = Unit::new("h", &["hour", "hours"], Dimension::Time, 3600.0);
+    /// Twenty-four hours
+    pub const DAY: Unit = Unit::new("d", &["day", "days"], Dimension::Time, 86400.0);
+    /// One hundredth of a whole
+    pub const PERCENT: Unit = Unit::new("%", &["percent"], Dimension::Ratio, 1e-2);
+    /// One thousandth of a whole
+    pub const PERMILLE: Unit = Unit::new("‰", &["permille"], Dimension::Ratio, 1e-3);
+
+    /// All known units
+    pub const ALL: &'static [Unit] = &[
+        Self::MILLIGRAM,
+        Self::GRAM,
+        Self::KILOGRAM,
+        Self::TONNE,
+        Self::OUNCE,
+        Self::POUND,
+        Self::MILLIMETRE,
+        Self::CENTIMETRE,
+        Self::METRE,
+        Self::KILOMETRE,
+        Self::INCH,
+        Self::FOOT,
+        Self::MILE,
+        Self::MILLISECOND,
+        Self::SECOND,
+        Self::MINUTE,
+        Self::HOUR,
+        Self::DAY,
+        Self::PERCENT,
+        Self::PERMILLE,
+    ];
+
+    /// Create a new Unit
+    pub const fn new(
+        symbol: &'static str,
+        names: &'static [&'static str],
+        dimension: Dimension,
+        factor: f64,
+    ) -> Self {
+        Self {
+            symbol,
+            names,
+            dimension,
+            factor,
+        }
+    }
+
+    /// Find a known unit by its symbol, or by one of its names ignoring case
+    pub fn find(word: &str) -> Option<&'static Unit> {
+        Self::ALL
+            .iter()
+            .find(|unit| unit.symbol == word)
+            .or_else(|| {
+                Self::ALL.iter().find(|unit| {
+                    unit.names
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(word))
+                })
+            })
+    }
+}
+
+impl<D> Quantity<D>
+where
+    D: Measure,
+{
+    /// Create a new Quantity. Returns `None` if the unit is not of dimension `D`
+    pub fn new(value: f64, unit: &'static Unit) -> Option<Self> {
+        if unit.dimension == D::DIMENSION {
+            Some(Self {
+                value,
+                unit,
+                dimension: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get the value in the unit the quantity was created with
+    #[inline]
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Get the unit the quantity was created with
+    #[inline]
+    pub fn unit(&self) -> &'static Unit {
+        self.unit
+    }
+
+    /// Get the value converted into another unit. Returns `None` if the unit is not of dimension `D`
+    pub fn value_in(&self, unit: &Unit) -> Option<f64> {
+        if unit.dimension == D::DIMENSION {
+            Some(self.value * self.unit.factor / unit.factor)
+        } else {
+            None
+        }
+    }
+
+    /// Convert into another unit. Returns `None` if the unit is not of dimension `D`
+    pub fn to(&self, unit: &'static Unit) -> Option<Self> {
+        Self::new(self.value_in(unit)?, unit)
+    }
+
+    fn base_value(&self) -> f64 {
+        self.value * self.unit.factor
+    }
+}
+
+impl<D> PartialEq for Quantity<D>
+where
+    D: Measure,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.base_value() == other.base_value()
+    }
+}
+
+impl<D> PartialOrd for Quantity<D>
+where
+    D: Measure,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.base_value().partial_cmp(&other.base_value())
+    }
+}
+
+impl<'a, D> MatchData<'a> for Quantity<D>
+where
+    D: Measure,
+{
+    fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        let start = matcher.clone();
+        let word = matcher.clone().next_static()?;
+        let split = word
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '+')))
+            .unwrap_or(word.len());
+        let (value, mut mismatch, symbol) = if split > 0 && split < word.len() {
+            let value = match numeral(&word[..split]) {
+                Some((value, false)) => value,
+                _ => return Err(MatchError::InvalidData),
+            };
+            matcher.next_static()?;
+            let mut mismatch = start.mismatch(D::DIMENSION.name());
+            mismatch.offset += split;
+            mismatch.column += split;
+            (value, mismatch, &word[split..])
+        } else {
+            let value = next_cardinal(matcher)?;
+            let mismatch = matcher.mismatch(D::DIMENSION.name());
+            (value, mismatch, matcher.next_static()?)
+        };
+        let value = value.to_float();
+        mismatch.found = symbol.into();
+        match Unit::find(symbol) {
+            Some(unit) => Self::new(value, unit).ok_or(MatchError::IncompatibleUnit(mismatch)),
+            None => Err(MatchError::UnknownUnit(mismatch)),
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl<D> fmt::Display for Quantity<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("{} {}", self.value, self.unit.symbol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_quantity() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("3 kilograms and 250ms");
+        let mass = matcher.next_data::<Quantity<Mass>>()?;
+        assert_eq!(mass.value(), 3.0);
+        assert_eq!(mass.unit(), &Unit::KILOGRAM);
+        assert_eq!(mass.value_in(&Unit::GRAM), Some(3000.0));
+        matcher.next_word("and")?;
+        let time = matcher.next_data::<Quantity<Time>>()?;
+        assert_eq!(time.value(), 250.0);
+        assert_eq!(time.unit(), &Unit::MILLISECOND);
+        assert!(matcher.is_empty());
+        let mut matcher = Matcher::new("10 %");
+        let ratio = matcher.next_data::<Quantity<Ratio>>()?;
+        assert_eq!(ratio.value_in(&Unit::PERMILLE), Some(100.0));
+        assert_eq!(ratio.to_string(), "10 %");
+        let mut matcher = Matcher::new("three kilograms and 1,500 g and 2,000mg");
+        let mass = matcher.next_data::<Quantity<Mass>>()?;
+        assert_eq!(mass.value_in(&Unit::GRAM), Some(3000.0));
+        matcher.next_word("and")?;
+        assert_eq!(matcher.next_data::<Quantity<Mass>>()?.value(), 1500.0);
+        matcher.next_word("and")?;
+        assert_eq!(matcher.next_data::<Quantity<Mass>>()?.value(), 2000.0);
+        Ok(())
+    }
+
+    #[test]
+    fn incompatible_unit() {
+        let mut matcher = Matcher::new("weighs 250ms");
+        matcher.next_word("weighs").unwrap();
+        match matcher.next_data::<Quantity<Mass>>() {
+            Err(MatchError::IncompatibleUnit(mismatch)) => {
+                assert_eq!(mismatch.column, 11);
+                assert_eq!(mismatch.expected, "mass");
+                assert_eq!(mismatch.found, "ms");
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        let mut matcher = Matcher::new("3 parsecs");
+        assert!(matches!(
+            matcher.next_data::<Quantity<Length>>(),
+            Err(MatchError::UnknownUnit(_))
+        ));
+        let mut matcher = Matcher::new("kg");
+        assert!(matches!(
+            matcher.next_data::<Quantity<Mass>>(),
+            Err(MatchError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn convert() {
+        let length = Quantity::<Length>::new(1.0, &Unit::MILE).unwrap();
+        assert_eq!(length.to(&Unit::METRE).unwrap().value(), 1609.344);
+        assert!(length.to(&Unit::SECOND).is_none());
+        assert!(Quantity::<Length>::new(1.0, &Unit::KILOGRAM).is_none());
+        assert!(length > Quantity::new(1.0, &Unit::KILOMETRE).unwrap());
+    }
+}
@@ -0,0 +1,231 @@
+//! Tag expressions such as "@smoke and not @slow" which select scripts by their tags
+
+use crate::matcher::{MatchError, Mismatch};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A boolean expression over tags. `not` binds tighter than `and`, which binds tighter than `or`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TagExpression {
+    /// Holds if the tag is present, such as "@smoke"
+    Tag(String),
+    /// Holds if the expression does not
+    Not(Box<TagExpression>),
+    /// Holds if both expressions do
+    And(Box<TagExpression>, Box<TagExpression>),
+    /// Holds if either expression does
+    Or(Box<TagExpression>, Box<TagExpression>),
+}
+
+/// The deepest that `not` and parentheses may nest in a tag expression
+const MAX_DEPTH: usize = 64;
+
+/// Reads a tag expression token by token
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl TagExpression {
+    /// Parse a tag expression such as "@smoke and not (@slow or @wip)". `not` and parentheses may
+    /// nest at most 64 deep, past which only a tag is expected
+    pub fn parse(src: &str) -> Result<Self, MatchError> {
+        let mut parser = Parser::new(src);
+        let expression = parser.or()?;
+        match parser.peek() {
+            Some(_) => Err(parser.error("")),
+            None => Ok(expression),
+        }
+    }
+
+    /// Check if the expression holds for a list of tags
+    pub fn matches<S>(&self, tags: &[S]) -> bool
+    where
+        S: AsRef<str>,
+    {
+        match self {
+            Self::Tag(tag) => tags.iter().any(|t| t.as_ref() == tag),
+            Self::Not(expression) => !expression.matches(tags),
+            Self::And(a, b) => a.matches(tags) && b.matches(tags),
+            Self::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (i, c) in src.char_indices() {
+            if c.is_whitespace() || c == '(' || c == ')' {
+                if let Some(start) = start.take() {
+                    tokens.push((start, &src[start..i]));
+                }
+                if !c.is_whitespace() {
+                    tokens.push((i, &src[i..i + 1]));
+                }
+            } else if start.is_none() {
+                start = Some(i);
+            }
+        }
+        if let Some(start) = start {
+            tokens.push((start, &src[start..]));
+        }
+        Self {
+            src,
+            tokens,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|(_, token)| *token)
+    }
+
+    fn next_if(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<TagExpression, MatchError> {
+        let mut expression = self.and()?;
+        while self.next_if("or") {
+            expression = TagExpression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<TagExpression, MatchError> {
+        let mut expression = self.not()?;
+        while self.next_if("and") {
+            expression = TagExpression::And(Box::new(expression), Box::new(self.not()?));
+        }
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<TagExpression, MatchError> {
+        if self.peek() == Some("not") {
+            let expression = self.nested(Self::not)?;
+            Ok(TagExpression::Not(Box::new(expression)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<TagExpression, MatchError> {
+        if self.peek() == Some("(") {
+            let expression = self.nested(Self::or)?;
+            return if self.next_if(")") {
+                Ok(expression)
+            } else {
+                Err(self.error(")"))
+            };
+        }
+        match self.peek() {
+            Some(tag) if tag.len() > 1 && tag.starts_with('@') => {
+                self.pos += 1;
+                Ok(TagExpression::Tag(tag.into()))
+            }
+            _ => Err(self.error("tag")),
+        }
+    }
+
+    /// Skip the current token and parse the expression it opens one level deeper
+    fn nested<F>(&mut self, parse: F) -> Result<TagExpression, MatchError>
+    where
+        F: FnOnce(&mut Self) -> Result<TagExpression, MatchError>,
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("tag"));
+        }
+        self.pos += 1;
+        self.depth += 1;
+        let expression = parse(self);
+        self.depth -= 1;
+        expression
+    }
+
+    /// Describe a mismatch at the current token
+    fn error(&self, expected: &str) -> MatchError {
+        let (offset, found) = self
+            .tokens
+            .get(self.pos)
+            .copied()
+            .unwrap_or((self.src.len(), ""));
+        MatchError::InvalidTagExpression(Mismatch {
+            offset,
+            line: 0,
+            column: self.src[..offset].chars().count() + 1,
+            expected: expected.into(),
+            found: found.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn matches() -> Result<(), MatchError> {
+        let expression = TagExpression::parse("@smoke and not @slow")?;
+        assert!(expression.matches(&["@smoke"]));
+        assert!(!expression.matches(&["@smoke", "@slow"]));
+        assert!(!expression.matches::<&str>(&[]));
+        let expression = TagExpression::parse("not (@slow or @wip) and @smoke or @all")?;
+        assert!(expression.matches(&["@smoke"]));
+        assert!(!expression.matches(&["@smoke", "@wip"]));
+        assert!(expression.matches(&["@all", "@wip"]));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        for (src, message) in &[
+            (
+                "@smoke and",
+                "invalid tag expression: expected 'tag' at column 11, found end of line",
+            ),
+            (
+                "(@smoke or @slow",
+                "invalid tag expression: expected ')' at column 17, found end of line",
+            ),
+            (
+                "@smoke @slow",
+                "invalid tag expression: expected end of line at column 8, found '@slow'",
+            ),
+            (
+                "smoke",
+                "invalid tag expression: expected 'tag' at column 1, found 'smoke'",
+            ),
+        ] {
+            assert_eq!(TagExpression::parse(src).unwrap_err().to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn nesting() {
+        let nested = |prefix: &str, suffix: &str, depth: usize| {
+            let mut src = prefix.repeat(depth);
+            src.push_str("@smoke");
+            src.push_str(&suffix.repeat(depth));
+            TagExpression::parse(&src)
+        };
+        assert!(nested("not ", "", MAX_DEPTH).is_ok());
+        assert!(nested("(", ")", MAX_DEPTH).is_ok());
+        assert_eq!(
+            nested("not ", "", 100_000).unwrap_err().to_string(),
+            "invalid tag expression: expected 'tag' at column 257, found 'not'"
+        );
+        assert!(nested("(", ")", 100_000).is_err());
+    }
+}
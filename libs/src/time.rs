@@ -0,0 +1,588 @@
+//! Dates, times and durations written in English, such as "March 3rd 2027", "2:30 pm" or
+//! "2 weeks". Relative forms such as "tomorrow" are resolved with the `Clock` of the match context
+
+pub use chrono;
+
+use crate::bdd::{Step, StepContext};
+use crate::matcher::{MatchContext, MatchData, MatchDataIn, MatchError, Matcher};
+use crate::number::{next_cardinal, Number};
+use chrono::{Datelike, Month, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+use core::convert::TryFrom;
+use core::fmt;
+
+/// A match context which supplies the current date and time to resolve relative dates and times
+pub trait Clock {
+    /// Get the current date and time. Contexts without a clock only accept absolute forms
+    fn now(&self) -> Option<NaiveDateTime> {
+        None
+    }
+}
+
+/// A clock which is stopped at a fixed date and time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FixedClock(pub NaiveDateTime);
+
+/// A BDD step paired with a clock, so that BDD functions resolve relative dates and times
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimedStep<K> {
+    /// The current BDD step
+    pub step: Step,
+    /// The clock of the script
+    pub clock: K,
+}
+
+/// A date such as "March 3rd 2027", "2027-03-03", "tomorrow", "next Monday" or "in 2 weeks"
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Date(pub NaiveDate);
+
+/// A time of day such as "14:30", "2:30 pm", "noon" or "now"
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Time(pub NaiveTime);
+
+/// A date followed by an optional time of day such as "March 3rd 2027 at 2 pm", or "now". Dates
+/// without a time of day start at midnight
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DateTime(pub NaiveDateTime);
+
+/// A duration such as "2 weeks" or "1 hour and 30 minutes"
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Duration(pub TimeDelta);
+
+impl Clock for () {}
+
+impl Clock for Step {}
+
+impl Clock for FixedClock {
+    #[inline]
+    fn now(&self) -> Option<NaiveDateTime> {
+        Some(self.0)
+    }
+}
+
+crate::match_context!(FixedClock);
+
+impl<K> TimedStep<K> {
+    /// Start a script at the initial BDD step with a clock
+    pub fn new(clock: K) -> Self {
+        Self {
+            step: Step::new(),
+            clock,
+        }
+    }
+}
+
+impl<K: Clock> Clock for TimedStep<K> {
+    #[inline]
+    fn now(&self) -> Option<NaiveDateTime> {
+        self.clock.now()
+    }
+}
+
+impl<K> StepContext for TimedStep<K> {
+    #[inline]
+    fn step(&self) -> Step {
+        self.step
+    }
+
+    #[inline]
+    fn set_step(&mut self, step: Step) {
+        self.step = step;
+    }
+}
+
+impl<K: Clone> MatchContext for TimedStep<K> {
+    type Checkpoint = Self;
+
+    #[inline]
+    fn checkpoint(&self) -> Self::Checkpoint {
+        self.clone()
+    }
+
+    #[inline]
+    fn rollback(&mut self, checkpoint: &Self::Checkpoint) {
+        self.clone_from(checkpoint);
+    }
+}
+
+impl<'a, C> MatchDataIn<'a, C> for Date
+where
+    C: Clock,
+{
+    fn match_data_in(ctx: &C, matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        next_date(ctx, matcher).map(Date)
+    }
+}
+
+impl<'a, C> MatchDataIn<'a, C> for Time
+where
+    C: Clock,
+{
+    fn match_data_in(ctx: &C, matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        next_time(ctx, matcher).map(Time)
+    }
+}
+
+impl<'a, C> MatchDataIn<'a, C> for DateTime
+where
+    C: Clock,
+{
+    fn match_data_in(ctx: &C, matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        if next_if(matcher, |word| is_word(word, "now")).is_some() {
+            return now(ctx).map(DateTime);
+        }
+        let date = next_date(ctx, matcher)?;
+        let mut at = matcher.clone();
+        let time = match next_if(&mut at, |word| is_word(word, "at")) {
+            Some(_) => next_time(ctx, &mut at).ok(),
+            None => None,
+        };
+        match time {
+            Some(time) => {
+                *matcher = at;
+                Ok(DateTime(date.and_time(time)))
+            }
+            None => Ok(DateTime(date.and_time(NaiveTime::MIN))),
+        }
+    }
+}
+
+impl<'a> MatchData<'a> for Duration {
+    fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        let mut total = next_duration(matcher)?;
+        loop {
+            let mut next = matcher.clone();
+            next_if(&mut next, |word| is_word(word, "and"));
+            match next_duration(&mut next) {
+                Ok(duration) => {
+                    total = total
+                        .checked_add(&duration)
+                        .ok_or(MatchError::InvalidData)?;
+                    *matcher = next;
+                }
+                Err(_) => return Ok(Duration(total)),
+            }
+        }
+    }
+}
+
+fn now<C>(ctx: &C) -> Result<NaiveDateTime, MatchError>
+where
+    C: Clock,
+{
+    ctx.now().ok_or(MatchError::MissingClock)
+}
+
+fn next_date<C>(ctx: &C, matcher: &mut Matcher) -> Result<NaiveDate, MatchError>
+where
+    C: Clock,
+{
+    let mut relative = matcher.clone();
+    if let Ok(count) = next_count(&mut relative) {
+        let unit = next_word(&mut relative)?;
+        let forward = if next_if(&mut relative, |word| is_word(word, "ago")).is_some() {
+            Some(false)
+        } else {
+            let mut from_now = relative.clone();
+            if next_if(&mut from_now, |word| is_word(word, "from")).is_some()
+                && next_if(&mut from_now, |word| is_word(word, "now")).is_some()
+            {
+                relative = from_now;
+                Some(true)
+            } else {
+                None
+            }
+        };
+        if let Some(forward) = forward {
+            let date = shift(now(ctx)?.date(), count, unit, forward)?;
+            *matcher = relative;
+            return Ok(date);
+        }
+    }
+    let word = next_word(matcher)?;
+    let today = || now(ctx).map(|now| now.date());
+    match word.to_ascii_lowercase().as_str() {
+        "today" => return today(),
+        "tomorrow" => return shift(today()?, 1, "day", true),
+        "yesterday" => return shift(today()?, 1, "day", false),
+        "in" => {
+            let count = next_count(matcher)?;
+            let unit = next_word(matcher)?;
+            return shift(today()?, count, unit, true);
+        }
+        direction @ "next" | direction @ "last" | direction @ "this" => {
+            let weekday = next_word(matcher)?
+                .parse::<Weekday>()
+                .map_err(|_| MatchError::InvalidData)?;
+            return weekday_from(today()?, weekday, direction);
+        }
+        _ => {}
+    }
+    let (year, month, day) = if let Some(date) = iso_date(word) {
+        date
+    } else if let Ok(month) = word.parse::<Month>() {
+        let day = day(next_word(matcher)?).ok_or(MatchError::InvalidData)?;
+        (next_if(matcher, year), month.number_from_month(), day)
+    } else if let Some(day) = day(word) {
+        next_if(matcher, |word| is_word(word, "of"));
+        let month = next_word(matcher)?
+            .parse::<Month>()
+            .map_err(|_| MatchError::InvalidData)?;
+        (next_if(matcher, year), month.number_from_month(), day)
+    } else {
+        return Err(MatchError::InvalidData);
+    };
+    let year = match year {
+        Some(year) => year,
+        None => now(ctx)?.year(),
+    };
+    NaiveDate::from_ymd_opt(year, month, day).ok_or(MatchError::InvalidData)
+}
+
+fn next_time<C>(ctx: &C, matcher: &mut Matcher) -> Result<NaiveTime, MatchError>
+where
+    C: Clock,
+{
+    let word = next_word(matcher)?;
+    match word.to_ascii_lowercase().as_str() {
+        "now" => return now(ctx).map(|now| now.time()),
+        "noon" | "midday" => return Ok(NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+        "midnight" => return Ok(NaiveTime::MIN),
+        _ => {}
+    }
+    let (clock, pm) = match meridiem_suffix(word) {
+        Some((clock, pm)) => (clock, Some(pm)),
+        None => (word, next_if(matcher, meridiem)),
+    };
+    let is_full_hour = pm.is_some() || next_if(matcher, |word| is_word(word, "o'clock")).is_some();
+    let mut parts = clock.splitn(3, ':');
+    let mut part = || parts.next().map(|part| part.parse::<u32>().ok());
+    let hour = part().flatten().ok_or(MatchError::InvalidData)?;
+    let (minute, second) = match (part(), part()) {
+        (None, _) if is_full_hour => (0, 0),
+        (Some(Some(minute)), None) => (minute, 0),
+        (Some(Some(minute)), Some(Some(second))) => (minute, second),
+        _ => return Err(MatchError::InvalidData),
+    };
+    let hour = match pm {
+        Some(pm) if (1..=12).contains(&hour) => hour % 12 + if pm { 12 } else { 0 },
+        Some(_) => return Err(MatchError::InvalidData),
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second).ok_or(MatchError::InvalidData)
+}
+
+fn next_duration(matcher: &mut Matcher) -> Result<TimeDelta, MatchError> {
+    let count = next_count(matcher)?;
+    let millis = match next_word(matcher)?.to_ascii_lowercase().as_str() {
+        "ms" | "millisecond" | "milliseconds" => 1,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1000,
+        "min" | "mins" | "minute" | "minutes" => 60_000,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3_600_000,
+        "day" | "days" => 86_400_000,
+        "week" | "weeks" => 604_800_000,
+        _ => return Err(MatchError::InvalidData),
+    };
+    i64::from(count)
+        .checked_mul(millis)
+        .and_then(TimeDelta::try_milliseconds)
+        .ok_or(MatchError::InvalidData)
+}
+
+/// Move a date by a number of days, weeks, months or years
+fn shift(date: NaiveDate, count: u32, unit: &str, forward: bool) -> Result<NaiveDate, MatchError> {
+    let shifted = match unit.to_ascii_lowercase().trim_end_matches('s') {
+        "day" => TimeDelta::try_days(count.into()).and_then(|days| {
+            if forward {
+                date.checked_add_signed(days)
+            } else {
+                date.checked_sub_signed(days)
+            }
+        }),
+        "week" => TimeDelta::try_weeks(count.into()).and_then(|weeks| {
+            if forward {
+                date.checked_add_signed(weeks)
+            } else {
+                date.checked_sub_signed(weeks)
+            }
+        }),
+        unit @ "month" | unit @ "year" => {
+            let months = if unit == "year" {
+                count.checked_mul(12)
+            } else {
+                Some(count)
+            };
+            months.map(Months::new).and_then(|months| {
+                if forward {
+                    date.checked_add_months(months)
+                } else {
+                    date.checked_sub_months(months)
+                }
+            })
+        }
+        _ => None,
+    };
+    shifted.ok_or(MatchError::InvalidData)
+}
+
+/// Find the weekday after ("next"), before ("last") or on or after ("this") a date
+fn weekday_from(
+    date: NaiveDate,
+    weekday: Weekday,
+    direction: &str,
+) -> Result<NaiveDate, MatchError> {
+    let from = date.weekday().num_days_from_monday();
+    let to = weekday.num_days_from_monday();
+    let (days, forward) = match direction {
+        "last" => ((from + 7 - to - 1) % 7 + 1, false),
+        "next" => ((to + 7 - from - 1) % 7 + 1, true),
+        _ => ((to + 7 - from) % 7, true),
+    };
+    shift(date, days, "day", forward)
+}
+
+fn iso_date(word: &str) -> Option<(Option<i32>, u32, u32)> {
+    let mut parts = word.splitn(3, '-');
+    let year = parts.next()?;
+    if year.len() != 4 {
+        return None;
+    }
+    let year = year.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Some((Some(year), month, day))
+}
+
+/// Read the next word, dropping a trailing comma
+fn next_word<'a>(matcher: &mut Matcher<'a>) -> Result<&'a str, MatchError> {
+    Ok(matcher.next_static()?.trim_end_matches(','))
+}
+
+/// Read the next word if `f` accepts it, otherwise leave the matcher untouched
+fn next_if<'a, T, F>(matcher: &mut Matcher<'a>, f: F) -> Option<T>
+where
+    F: FnOnce(&'a str) -> Option<T>,
+{
+    let mut next = matcher.clone();
+    let out = f(next_word(&mut next).ok()?)?;
+    *matcher = next;
+    Some(out)
+}
+
+fn is_word(word: &str, expected: &str) -> Option<()> {
+    if word.eq_ignore_ascii_case(expected) {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Read the count of a duration or of a relative date, such as "a", "2" or "two"
+fn next_count(matcher: &mut Matcher) -> Result<u32, MatchError> {
+    if next_if(matcher, |word| {
+        is_word(word, "a").or_else(|| is_word(word, "an"))
+    })
+    .is_some()
+    {
+        return Ok(1);
+    }
+    let mut next = matcher.clone();
+    let count = match next_cardinal(&mut next)? {
+        Number::Int(count) => u32::try_from(count).ok(),
+        Number::UInt(count) => u32::try_from(count).ok(),
+        Number::Float(_) => None,
+    };
+    *matcher = next;
+    count.ok_or(MatchError::InvalidData)
+}
+
+fn day(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &word[digits.len()..];
+    let is_ordinal = ["", "st", "nd", "rd", "th"]
+        .iter()
+        .any(|ordinal| suffix.eq_ignore_ascii_case(ordinal));
+    match digits.parse() {
+        Ok(day) if is_ordinal && digits.bytes().all(|b| b.is_ascii_digit()) => Some(day),
+        _ => None,
+    }
+}
+
+fn year(word: &str) -> Option<i32> {
+    if word.len() == 4 && word.bytes().all(|b| b.is_ascii_digit()) {
+        word.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn meridiem(word: &str) -> Option<bool> {
+    if word.eq_ignore_ascii_case("am") || word.eq_ignore_ascii_case("a.m.") {
+        Some(false)
+    } else if word.eq_ignore_ascii_case("pm") || word.eq_ignore_ascii_case("p.m.") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+fn meridiem_suffix(word: &str) -> Option<(&str, bool)> {
+    let split = word.len().checked_sub(2).filter(|split| *split > 0)?;
+    if !word.is_char_boundary(split) {
+        return None;
+    }
+    Some((&word[..split], meridiem(&word[split..])?))
+}
+
+impl fmt::Display for Date {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Time {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for DateTime {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Duration {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock() -> FixedClock {
+        // a Wednesday
+        FixedClock(
+            NaiveDate::from_ymd_opt(2026, 10, 14)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap(),
+        )
+    }
+
+    fn date(ctx: &impl Clock, src: &str) -> Result<NaiveDate, MatchError> {
+        let mut matcher = Matcher::new(src);
+        let Date(date) = matcher.next_data_in(ctx)?;
+        assert!(matcher.is_empty(), "{}", src);
+        Ok(date)
+    }
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn absolute_dates() -> Result<(), MatchError> {
+        for src in [
+            "March 3rd 2027",
+            "March 3, 2027",
+            "3 March 2027",
+            "3rd of march 2027",
+            "2027-03-03",
+        ] {
+            assert_eq!(date(&(), src)?, ymd(2027, 3, 3));
+        }
+        assert_eq!(date(&clock(), "Mar 3")?, ymd(2026, 3, 3));
+        assert!(matches!(date(&(), "Mar 3"), Err(MatchError::MissingClock)));
+        assert!(matches!(
+            date(&(), "February 30 2027"),
+            Err(MatchError::InvalidData)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn relative_dates() -> Result<(), MatchError> {
+        let ctx = clock();
+        assert_eq!(date(&ctx, "today")?, ymd(2026, 10, 14));
+        assert_eq!(date(&ctx, "tomorrow")?, ymd(2026, 10, 15));
+        assert_eq!(date(&ctx, "yesterday")?, ymd(2026, 10, 13));
+        assert_eq!(date(&ctx, "next Monday")?, ymd(2026, 10, 19));
+        assert_eq!(date(&ctx, "next Wednesday")?, ymd(2026, 10, 21));
+        assert_eq!(date(&ctx, "last Wednesday")?, ymd(2026, 10, 7));
+        assert_eq!(date(&ctx, "last friday")?, ymd(2026, 10, 9));
+        assert_eq!(date(&ctx, "this Wednesday")?, ymd(2026, 10, 14));
+        assert_eq!(date(&ctx, "in 2 weeks")?, ymd(2026, 10, 28));
+        assert_eq!(date(&ctx, "in a month")?, ymd(2026, 11, 14));
+        assert_eq!(date(&ctx, "3 days ago")?, ymd(2026, 10, 11));
+        assert_eq!(date(&ctx, "in two weeks")?, ymd(2026, 10, 28));
+        assert_eq!(date(&ctx, "1 year from now")?, ymd(2027, 10, 14));
+        assert!(matches!(
+            date(&(), "tomorrow"),
+            Err(MatchError::MissingClock)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn times() -> Result<(), MatchError> {
+        let ctx = clock();
+        for (src, hour, minute) in [
+            ("14:30", 14, 30),
+            ("2:30 pm", 14, 30),
+            ("2pm", 14, 0),
+            ("12 am", 0, 0),
+            ("9 o'clock", 9, 0),
+            ("noon", 12, 0),
+            ("now", 9, 30),
+        ] {
+            let mut matcher = Matcher::new(src);
+            let Time(time) = matcher.next_data_in(&ctx)?;
+            assert_eq!(time, NaiveTime::from_hms_opt(hour, minute, 0).unwrap());
+            assert!(matcher.is_empty());
+        }
+        let mut matcher = Matcher::new("14");
+        assert!(matcher.next_data_in::<_, Time>(&ctx).is_err());
+        let mut matcher = Matcher::new("13 pm");
+        assert!(matcher.next_data_in::<_, Time>(&ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn date_times() -> Result<(), MatchError> {
+        let ctx = clock();
+        let mut matcher = Matcher::new("tomorrow at 2:30 pm");
+        let DateTime(date_time) = matcher.next_data_in(&ctx)?;
+        assert_eq!(date_time, ymd(2026, 10, 15).and_hms_opt(14, 30, 0).unwrap());
+        let mut matcher = Matcher::new("March 3rd 2027 at the latest");
+        let DateTime(date_time) = matcher.next_data_in(&ctx)?;
+        assert_eq!(date_time, ymd(2027, 3, 3).and_time(NaiveTime::MIN));
+        assert_eq!(matcher.next_static()?, "at");
+        let mut matcher = Matcher::new("now");
+        let DateTime(date_time) = matcher.next_data_in(&ctx)?;
+        assert_eq!(date_time, ctx.0);
+        Ok(())
+    }
+
+    #[test]
+    fn durations() -> Result<(), MatchError> {
+        let mut matcher = Matcher::new("1 hour and 30 minutes later");
+        let Duration(duration) = matcher.next_data()?;
+        assert_eq!(duration, TimeDelta::try_minutes(90).unwrap());
+        assert_eq!(matcher.next_static()?, "later");
+        let mut matcher = Matcher::new("2 weeks, 1 day");
+        let Duration(duration) = matcher.next_data()?;
+        assert_eq!(duration, TimeDelta::try_days(15).unwrap());
+        let mut matcher = Matcher::new("two weeks and a day");
+        let Duration(duration) = matcher.next_data()?;
+        assert_eq!(duration, TimeDelta::try_days(15).unwrap());
+        let mut matcher = Matcher::new("2 fortnights");
+        assert!(matcher.next_data::<Duration>().is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 //! Holds the mutable state of the Virtual Machine
 
 use super::trap::Trap;
+use crate::matcher::MatchContext;
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
@@ -81,6 +82,17 @@ impl Context {
     }
 }
 
+/// Globals are values of any type which cannot be saved, so matching should only read them
+impl MatchContext for Context {
+    type Checkpoint = ();
+
+    #[inline]
+    fn checkpoint(&self) -> Self::Checkpoint {}
+
+    #[inline]
+    fn rollback(&mut self, _: &Self::Checkpoint) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -7,5 +7,5 @@ mod trap;
 
 pub use context::Context;
 pub use func::{Callable, Func};
-pub use script::{Instance, Script};
+pub use script::{Instance, Metadata, Script};
 pub use trap::Trap;
@@ -10,6 +10,22 @@ use alloc::vec::Vec;
 #[derive(Default)]
 pub struct Script<'a> {
     funcs: Vec<Func<'a>>,
+    metadata: Metadata<'a>,
+}
+
+/// The front-matter header of a Script
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Metadata<'a> {
+    /// The title of the script
+    pub title: Option<&'a str>,
+    /// The version of the script
+    pub version: Option<&'a str>,
+    /// The author of the script
+    pub author: Option<&'a str>,
+    /// What the script does
+    pub description: Option<&'a str>,
+    /// The tags of the script, such as "@smoke"
+    pub tags: Vec<&'a str>,
 }
 
 /// The current state of the script instance
@@ -34,7 +50,7 @@ impl<'a> Script<'a> {
 
     /// Create an instance of a Script
     #[inline]
-    pub fn instance(&self) -> Instance {
+    pub fn instance(&self) -> Instance<'_, 'a> {
         Instance {
             script: self,
             state: InstanceState::default(),
@@ -46,6 +62,18 @@ impl<'a> Script<'a> {
     pub fn push(&mut self, func: impl Callable + 'static) {
         self.funcs.push(Box::new(func));
     }
+
+    /// Get the metadata of the Script
+    #[inline]
+    pub fn metadata(&self) -> &Metadata<'a> {
+        &self.metadata
+    }
+
+    /// Get a mutable reference to the metadata of the Script
+    #[inline]
+    pub fn metadata_mut(&mut self) -> &mut Metadata<'a> {
+        &mut self.metadata
+    }
 }
 
 impl<'s, 'a> Instance<'s, 'a> {
@@ -123,9 +151,19 @@ impl InstanceState {
     }
 }
 
+/// A Script called as a function calls each of its functions in turn with the same context
+impl<'a> Callable for Script<'a> {
+    fn call(&self, ctx: &mut Context) -> Result<(), Trap> {
+        self.funcs.iter().try_for_each(|func| func.call(ctx))
+    }
+}
+
 impl<'a> From<Vec<Func<'a>>> for Script<'a> {
     fn from(funcs: Vec<Func<'a>>) -> Self {
-        Self { funcs }
+        Self {
+            funcs,
+            metadata: Metadata::default(),
+        }
     }
 }
 
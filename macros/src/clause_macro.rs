@@ -1,5 +1,5 @@
-use ogma_libs::clause::{parse, ParseError, Token};
-use proc_macro2::TokenStream;
+use ogma_libs::clause::{parse, ParseError, Span as ClauseSpan, Token};
+use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use syn::parse::{Parse, ParseStream};
 use syn::{
@@ -17,7 +17,11 @@ pub struct ClauseArgs {
 pub enum TokenOwned {
     Static(String),
     QueryVar(String),
-    DataVar(String),
+    DataVar(String, Option<String>),
+    TextVar(String),
+    ListVar(String, Option<String>),
+    Optional(String),
+    Alternatives(String),
 }
 
 impl<'a> From<Token<'a>> for TokenOwned {
@@ -25,7 +29,11 @@ impl<'a> From<Token<'a>> for TokenOwned {
         match t {
             Token::Static(s) => TokenOwned::Static(s.to_owned()),
             Token::QueryVar(s) => TokenOwned::QueryVar(s.to_owned()),
-            Token::DataVar(s) => TokenOwned::DataVar(s.to_owned()),
+            Token::DataVar(s, ty) => TokenOwned::DataVar(s.to_owned(), ty.map(str::to_owned)),
+            Token::TextVar(s) => TokenOwned::TextVar(s.to_owned()),
+            Token::ListVar(s, ty) => TokenOwned::ListVar(s.to_owned(), ty.map(str::to_owned)),
+            Token::Optional(s) => TokenOwned::Optional(s.to_owned()),
+            Token::Alternatives(s) => TokenOwned::Alternatives(s.to_owned()),
         }
     }
 }
@@ -35,11 +43,49 @@ impl ToTokens for TokenOwned {
         tokens.extend(match self {
             TokenOwned::Static(s) => quote! { ::ogma::clause::Token::Static(#s) },
             TokenOwned::QueryVar(s) => quote! { ::ogma::clause::Token::QueryVar(#s) },
-            TokenOwned::DataVar(s) => quote! { ::ogma::clause::Token::DataVar(#s) },
+            TokenOwned::DataVar(s, ty) => {
+                let ty = OptionStr(ty);
+                quote! { ::ogma::clause::Token::DataVar(#s, #ty) }
+            }
+            TokenOwned::TextVar(s) => quote! { ::ogma::clause::Token::TextVar(#s) },
+            TokenOwned::ListVar(s, ty) => {
+                let ty = OptionStr(ty);
+                quote! { ::ogma::clause::Token::ListVar(#s, #ty) }
+            }
+            TokenOwned::Optional(s) => quote! { ::ogma::clause::Token::Optional(#s) },
+            TokenOwned::Alternatives(s) => quote! { ::ogma::clause::Token::Alternatives(#s) },
         });
     }
 }
 
+struct OptionStr<'a>(&'a Option<String>);
+
+impl<'a> ToTokens for OptionStr<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self.0 {
+            Some(s) => quote! { ::core::option::Option::Some(#s) },
+            None => quote! { ::core::option::Option::None },
+        });
+    }
+}
+
+/// Get the span of a part of a clause string literal. Falls back to the span of the whole literal
+/// if the compiler does not support sub-spans or the literal contains escape sequences
+pub fn clause_span(lit: &LitStr, span: ClauseSpan) -> Span {
+    let repr = lit.token().to_string();
+    let value = lit.value();
+    let start = match repr.find('"') {
+        Some(quote) => quote + 1,
+        None => return lit.span(),
+    };
+    if repr.get(start..start + value.len()) != Some(value.as_str()) {
+        return lit.span();
+    }
+    lit.token()
+        .subspan(start + span.start..start + span.end)
+        .unwrap_or_else(|| lit.span())
+}
+
 impl Parse for ClauseArgs {
     fn parse(input: ParseStream) -> Result<Self, Error> {
         let vis = input.parse()?;
@@ -50,7 +96,7 @@ impl Parse for ClauseArgs {
         let clause_str = clause_lit_str.value();
         let tokens = parse(&clause_str)
             .collect::<Result<Vec<Token>, ParseError>>()
-            .map_err(|e| Error::new(clause_lit_str.span(), e))?
+            .map_err(|e| Error::new(clause_span(&clause_lit_str, e.span()), e))?
             .into_iter()
             .map(TokenOwned::from)
             .collect();
@@ -1,11 +1,12 @@
+use crate::clause_macro::clause_span;
 use ogma_libs::clause;
 use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::{
-    token, Attribute, Error, FnArg, GenericParam, Generics, Ident, ItemFn, Lifetime, LifetimeDef,
-    LitStr, Pat, Path, Type, Visibility,
+    token, Attribute, Error, FnArg, GenericArgument, GenericParam, Generics, Ident, ItemFn,
+    Lifetime, LifetimeDef, LitInt, LitStr, Pat, Path, PathArguments, Type, Visibility,
 };
 
 pub enum Bdd {
@@ -14,11 +15,31 @@ pub enum Bdd {
     Then,
 }
 
+impl Bdd {
+    fn keywords(&self) -> &'static str {
+        match self {
+            Bdd::Given => "Given|And|But|*",
+            Bdd::When => "When|And|But|*",
+            Bdd::Then => "Then|And|But|*",
+        }
+    }
+}
+
 pub struct Descriptor {
     attrs: Vec<Attribute>,
     name: Ident,
-    clause_span: Span,
+    clause: LitStr,
     clause_str: String,
+    priority: Option<i32>,
+    parsers: Vec<VarParser>,
+}
+
+/// A function which reads a data or list variable in place of `MatchData`, such as `price =
+/// parse_money` in `parse(price = parse_money)`
+#[derive(Clone)]
+pub struct VarParser {
+    name: Ident,
+    path: Path,
 }
 
 impl Descriptor {
@@ -27,51 +48,56 @@ impl Descriptor {
     }
 
     fn clause(&self) -> LitStr {
-        LitStr::new(&self.clause_str, self.clause_span)
+        self.clause.clone()
     }
 
     fn attrs(&self) -> Vec<Attribute> {
         self.attrs.clone()
     }
 
-    fn parse_clause(&self) -> Result<Vec<clause::Token>, Error> {
-        clause::parse(&self.clause_str)
-            .collect::<Result<Vec<clause::Token>, clause::ParseError>>()
-            .map_err(|e| Error::new(self.clause_span, e))
+    fn parse_clause_spanned(&self) -> Result<Vec<(clause::Token<'_>, Span)>, Error> {
+        clause::parse_spanned(&self.clause_str)
+            .map(|res| {
+                res.map(|(token, span)| (token, clause_span(&self.clause, span)))
+                    .map_err(|e| Error::new(clause_span(&self.clause, e.span()), e))
+            })
+            .collect()
     }
 
-    fn parse_var_names(&self) -> Result<Vec<Ident>, Error> {
-        self.parse_clause()?
+    fn parse_var_idents<F>(&self, is_var: F) -> Result<Vec<Ident>, Error>
+    where
+        F: Fn(&clause::Token) -> bool,
+    {
+        self.parse_clause_spanned()?
             .into_iter()
-            .filter_map(|t| match t {
-                clause::Token::Static(_) => None,
-                clause::Token::QueryVar(s) => Some(s),
-                clause::Token::DataVar(s) => Some(s),
+            .filter(|(token, _)| is_var(token))
+            .filter_map(|(token, span)| Some((token.var_name()?, span)))
+            .map(|(name, span)| {
+                let mut ident = syn::parse_str::<Ident>(name)?;
+                ident.set_span(span);
+                Ok(ident)
             })
-            .map(|s| syn::parse_str::<Ident>(s))
             .collect()
     }
 
+    fn parse_var_names(&self) -> Result<Vec<Ident>, Error> {
+        self.parse_var_idents(|token| token.var_name().is_some())
+    }
+
     fn parse_query_var_names(&self) -> Result<Vec<Ident>, Error> {
-        self.parse_clause()?
-            .into_iter()
-            .filter_map(|t| match t {
-                clause::Token::QueryVar(s) => Some(s),
-                _ => None,
-            })
-            .map(|s| syn::parse_str::<Ident>(s))
-            .collect()
+        self.parse_var_idents(|token| token.is_query_var())
     }
 
     fn parse_data_var_names(&self) -> Result<Vec<Ident>, Error> {
-        self.parse_clause()?
-            .into_iter()
-            .filter_map(|t| match t {
-                clause::Token::DataVar(s) => Some(s),
-                _ => None,
-            })
-            .map(|s| syn::parse_str::<Ident>(s))
-            .collect()
+        self.parse_var_idents(|token| token.is_data_var())
+    }
+
+    fn parse_text_var_names(&self) -> Result<Vec<Ident>, Error> {
+        self.parse_var_idents(|token| token.is_text_var())
+    }
+
+    fn parse_list_var_names(&self) -> Result<Vec<Ident>, Error> {
+        self.parse_var_idents(|token| token.is_list_var())
     }
 }
 
@@ -81,17 +107,44 @@ impl Parse for Descriptor {
         let name = input.parse()?;
         let _ = input.parse::<token::Comma>()?;
         let clause = input.parse::<LitStr>()?;
-        let clause_span = clause.span();
         let clause_str = clause.value();
+        let mut priority = None;
+        let mut parsers = Vec::new();
+        while input.parse::<Option<token::Comma>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if key == "priority" {
+                let _ = input.parse::<token::Eq>()?;
+                let negative = input.parse::<Option<token::Sub>>()?.is_some();
+                let value = input.parse::<LitInt>()?.base10_parse::<i32>()?;
+                priority = Some(if negative { -value } else { value });
+            } else if key == "parse" {
+                let content;
+                parenthesized!(content in input);
+                parsers.extend(content.parse_terminated::<_, token::Comma>(VarParser::parse)?);
+            } else {
+                return Err(Error::new(key.span(), "expected `priority` or `parse`"));
+            }
+        }
         Ok(Descriptor {
             attrs,
             name,
-            clause_span,
+            clause,
             clause_str,
+            priority,
+            parsers,
         })
     }
 }
 
+impl Parse for VarParser {
+    fn parse(input: ParseStream) -> Result<Self, Error> {
+        let name = input.parse()?;
+        let _ = input.parse::<token::Eq>()?;
+        let path = input.parse()?;
+        Ok(VarParser { name, path })
+    }
+}
+
 pub struct Func {
     inner: ItemFn,
 }
@@ -125,13 +178,26 @@ impl Func {
         Ok(self.parse_vars()?.into_iter().map(|v| v.name).collect())
     }
 
+    /// Get the trailing variable which is missing from the clause if it is a `DocString` or
+    /// `DataTable`, or an `Option` of one. It is bound to the doc string or data table of the step
+    fn parse_block_var(&self, desc: &Descriptor) -> Result<Option<FuncVar>, Error> {
+        let clause_names = desc.parse_var_names()?;
+        let vars = self.parse_vars()?;
+        Ok(vars
+            .last()
+            .filter(|var| {
+                vars.len() == clause_names.len() + 1
+                    && !clause_names.contains(&var.name)
+                    && var.is_block_type()
+            })
+            .cloned())
+    }
+
     fn parse_vars(&self) -> Result<Vec<FuncVar>, Error> {
         self.vars()
             .iter()
             .map(|v| match v {
-                FnArg::Receiver(r) => {
-                    return Err(Error::new(r.self_token.span, "invalid self"));
-                }
+                FnArg::Receiver(r) => Err(Error::new(r.self_token.span, "invalid self")),
                 FnArg::Typed(t) => {
                     let name = match t.pat.as_ref() {
                         Pat::Ident(n) => n.ident.clone(),
@@ -179,13 +245,11 @@ impl FuncVar {
         let mut expecting_lifetime = false;
         for token in self.ty.to_token_stream() {
             match token {
-                proc_macro2::TokenTree::Punct(punct) => {
-                    if punct.as_char() == '\'' {
-                        expecting_lifetime = true;
-                    }
+                proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                    expecting_lifetime = true;
                 }
                 proc_macro2::TokenTree::Ident(ident) => {
-                    if expecting_lifetime && ident.to_string() != "static" {
+                    if expecting_lifetime && ident != "static" {
                         return false;
                     }
                     expecting_lifetime = false;
@@ -197,6 +261,46 @@ impl FuncVar {
     }
 }
 
+impl FuncVar {
+    /// Get `T` if the type of the variable is `Vec<T>`
+    fn list_item_type(&self) -> Option<&Type> {
+        type_arg(&self.ty, "Vec")
+    }
+
+    /// Check if the type of the variable is `DocString` or `DataTable`, or an `Option` of either
+    fn is_block_type(&self) -> bool {
+        let is_block = |ty: &Type| matches!(type_name(ty), Some(name) if name == "DocString" || name == "DataTable");
+        is_block(&self.ty) || type_arg(&self.ty, "Option").is_some_and(is_block)
+    }
+}
+
+/// Get the name of a type without its path or arguments
+fn type_name(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(ty) => Some(&ty.path.segments.last()?.ident),
+        _ => None,
+    }
+}
+
+/// Get `T` if a type is `Name<T>`
+fn type_arg<'t>(ty: &'t Type, name: &str) -> Option<&'t Type> {
+    let path = match ty {
+        Type::Path(ty) => &ty.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 impl Parse for Func {
     fn parse(input: ParseStream) -> Result<Self, Error> {
         let inner = input.parse()?;
@@ -254,7 +358,7 @@ pub struct StructImpl {
 impl StructImpl {
     pub fn build(desc: &Descriptor, func: &Func) -> Self {
         let mut generics = func.generics();
-        generics.lifetimes_mut().for_each(|mut l| {
+        generics.lifetimes_mut().for_each(|l| {
             l.lifetime = Lifetime::new(&format!("'_{}", l.lifetime.ident), Span::call_site());
         });
         Self {
@@ -290,8 +394,14 @@ pub struct MatchImpl {
     lifetime: Lifetime,
     match_ctx: Path,
     bdd: Option<Bdd>,
+    clause: LitStr,
+    priority: Option<i32>,
     query_vars: Vec<FuncVar>,
     data_vars: Vec<FuncVar>,
+    text_vars: Vec<FuncVar>,
+    list_vars: Vec<FuncVar>,
+    block_var: Option<FuncVar>,
+    parsers: Vec<VarParser>,
 }
 
 impl MatchImpl {
@@ -307,38 +417,31 @@ impl MatchImpl {
             let lifetime = struct_generics.lifetimes().next().unwrap().lifetime.clone();
             (struct_generics.clone(), lifetime)
         };
-        let match_ctx = if bdd.is_none() {
-            let match_ctx = Ident::new("MCtx", Span::call_site());
-            impl_generics
-                .params
-                .push(GenericParam::Type(match_ctx.clone().into()));
-            match_ctx.into()
+        let match_ctx = Ident::new("MCtx", Span::call_site());
+        let param = if bdd.is_none() {
+            match_ctx.clone().into()
         } else {
-            parse_quote!(::ogma::bdd::Step)
+            parse_quote!(#match_ctx: ::ogma::bdd::StepContext)
         };
+        impl_generics.params.push(GenericParam::Type(param));
+        let match_ctx = match_ctx.into();
         let func_vars = func.parse_vars()?;
-        let query_vars = desc
-            .parse_query_var_names()?
-            .iter()
-            .map(|ident| {
-                func_vars
-                    .iter()
-                    .find(|var| &var.name == ident)
-                    .cloned()
-                    .ok_or_else(|| Error::new(ident.span(), "could not find ariable in func"))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
-        let data_vars = desc
-            .parse_data_var_names()?
-            .iter()
-            .map(|ident| {
-                func_vars
-                    .iter()
-                    .find(|var| &var.name == ident)
-                    .cloned()
-                    .ok_or_else(|| Error::new(ident.span(), "could not find ariable in func"))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+        let query_vars = find_vars(&desc.parse_query_var_names()?, &func_vars)?;
+        let data_vars = find_vars(&desc.parse_data_var_names()?, &func_vars)?;
+        let text_vars = find_vars(&desc.parse_text_var_names()?, &func_vars)?;
+        let list_vars = find_vars(&desc.parse_list_var_names()?, &func_vars)?;
+        for parser in &desc.parsers {
+            if !data_vars
+                .iter()
+                .chain(list_vars.iter())
+                .any(|var| var.name == parser.name)
+            {
+                return Err(Error::new(
+                    parser.name.span(),
+                    "expected a data or list variable of the clause",
+                ));
+            }
+        }
         Ok(Self {
             name: desc.name(),
             lifetime,
@@ -346,10 +449,24 @@ impl MatchImpl {
             impl_generics,
             match_ctx,
             bdd,
+            clause: desc.clause(),
+            priority: desc.priority,
             query_vars,
             data_vars,
+            text_vars,
+            list_vars,
+            block_var: func.parse_block_var(desc)?,
+            parsers: desc.parsers.clone(),
         })
     }
+
+    /// Get the function which reads a variable in place of `MatchData`
+    fn parser(&self, var: &FuncVar) -> Option<&Path> {
+        self.parsers
+            .iter()
+            .find(|parser| parser.name == var.name)
+            .map(|parser| &parser.path)
+    }
 }
 
 impl ToTokens for MatchImpl {
@@ -363,6 +480,9 @@ impl ToTokens for MatchImpl {
             .query_vars
             .iter()
             .chain(self.data_vars.iter())
+            .chain(self.text_vars.iter())
+            .chain(self.list_vars.iter())
+            .chain(self.block_var.iter())
             .map(|var| {
                 let name = &var.name;
                 quote! { let mut #name = None; }
@@ -371,6 +491,9 @@ impl ToTokens for MatchImpl {
             .query_vars
             .iter()
             .chain(self.data_vars.iter())
+            .chain(self.text_vars.iter())
+            .chain(self.list_vars.iter())
+            .chain(self.block_var.iter())
             .map(|var| {
                 let name = &var.name;
                 quote! { #name: #name.ok_or(::ogma::matcher::MatchError::UnfilledVar)?, }
@@ -387,59 +510,155 @@ impl ToTokens for MatchImpl {
         let data_var_matches = self.data_vars.iter().map(|var| {
             let name = &var.name;
             let name_str = name.to_string();
-            quote! { #name_str => #name = Some(m.next_data()?), }
+            match self.parser(var) {
+                Some(parser) => quote! { #name_str => #name = Some(#parser(m)?), },
+                None => quote! { #name_str => #name = Some(m.next_data_in(ctx)?), },
+            }
         });
-        let bdd_check = if let Some(ref bdd) = self.bdd {
-            let verb = match bdd {
-                Bdd::Given => "Given",
-                Bdd::When => "When",
-                Bdd::Then => "Then",
-            };
+        let data_var_bounds = self
+            .data_vars
+            .iter()
+            .filter(|var| self.parser(var).is_none())
+            .map(|var| {
+                let ty = &var.ty;
+                quote! { #ty: ::ogma::matcher::MatchDataIn<#lifetime, #match_ctx>, }
+            });
+        let block_var_bound = self.block_var.iter().map(|var| {
+            let ty = &var.ty;
+            quote! { #ty: ::ogma::block::MatchBlock<#lifetime>, }
+        });
+        let block_match = if let Some(ref var) = self.block_var {
+            let name = &var.name;
+            quote! { #name = Some(::ogma::block::MatchBlock::match_block(m.next_block())?); }
+        } else {
             quote! {
-                let token = match m.next_static()? {
-                    #verb => #verb,
-                    "And" => "And",
-                    _ => return Err(::ogma::matcher::MatchError::MismatchedStaticToken),
-                };
-                if let Some(next_state) = ctx.next(token) {
-                    *ctx = next_state;
-                } else {
-                    return Err(::ogma::matcher::MatchError::InvalidCtx);
+                if m.next_block().is_some() {
+                    return Err(::ogma::matcher::MatchError::UnexpectedBlock);
+                }
+            }
+        };
+        let text_var_matches = self.text_vars.iter().map(|var| {
+            let name = &var.name;
+            let name_str = name.to_string();
+            quote! { #name_str => #name = Some(m.next_text(Self::CLAUSE.get(index + 1))?.into()), }
+        });
+        let list_var_matches = self.list_vars.iter().map(|var| {
+            let name = &var.name;
+            let name_str = name.to_string();
+            match self.parser(var) {
+                Some(parser) => quote! {
+                    #name_str => #name = Some(m.next_list_with(Self::CLAUSE.get(index + 1), #parser)?),
+                },
+                None => quote! {
+                    #name_str => #name = Some(m.next_list_in(ctx, Self::CLAUSE.get(index + 1))?),
+                },
+            }
+        });
+        let list_var_bounds = self
+            .list_vars
+            .iter()
+            .filter(|var| self.parser(var).is_none())
+            .filter_map(|var| {
+                let ty = var.list_item_type()?;
+                Some(quote! { #ty: ::ogma::matcher::MatchDataIn<#lifetime, #match_ctx>, })
+            });
+        let clause = if let Some(ref bdd) = self.bdd {
+            let step = LitStr::new(
+                &format!("({}) {}", bdd.keywords(), self.clause.value()),
+                self.clause.span(),
+            );
+            quote! {
+                ::ogma::clause! {
+                    const STEP = #step;
                 }
+                &STEP
+            }
+        } else {
+            quote! { &Self::CLAUSE }
+        };
+        let priority = self.priority.map(|priority| {
+            quote! {
+                fn priority() -> i32 {
+                    #priority
+                }
+            }
+        });
+        let bdd_check = if let Some(ref bdd) = self.bdd {
+            let keywords = bdd.keywords();
+            quote! {
+                let token = m.next_alternative(#keywords)?;
+                let next_state = ::ogma::bdd::StepContext::step(ctx)
+                    .next(token)
+                    .ok_or(::ogma::matcher::MatchError::InvalidCtx)?;
             }
         } else {
             quote! {}
         };
+        let matched = if self.bdd.is_some() {
+            quote! {
+                let matched = #name {
+                    #(#var_assignments)*
+                };
+                ::ogma::bdd::StepContext::set_step(ctx, next_state);
+                Ok(matched)
+            }
+        } else {
+            quote! {
+                Ok(#name {
+                    #(#var_assignments)*
+                })
+            }
+        };
         tokens.extend(quote! {
-            impl #impl_generics ::ogma::matcher::Match<#lifetime, #match_ctx> for #name #struct_generics {
+            impl #impl_generics ::ogma::matcher::Match<#lifetime, #match_ctx> for #name #struct_generics
+            where
+                #(#data_var_bounds)*
+                #(#list_var_bounds)*
+                #(#block_var_bound)*
+            {
                 fn match_str(ctx: &mut #match_ctx, s: &#lifetime str) -> Result<Self, ::ogma::matcher::MatchError> {
-                    let mut m = ::ogma::matcher::Matcher::new(s);
+                    Self::match_with(ctx, ::ogma::matcher::Matcher::new(s))
+                }
+
+                fn clause() -> &'static [::ogma::clause::Token<'static>] {
+                    #clause
+                }
+
+                #priority
+
+                fn match_with(ctx: &mut #match_ctx, mut m: ::ogma::matcher::Matcher<#lifetime>) -> Result<Self, ::ogma::matcher::MatchError> {
                     #bdd_check
                     #(#var_declarations)*
-                    for token in &Self::CLAUSE {
-                        match *token {
-                            ::ogma::clause::Token::Static(token) => {
-                                if m.next_static()? != token {
-                                    return Err(::ogma::matcher::MatchError::MismatchedStaticToken);
-                                }
-                            },
+                    m.next_clause(&Self::CLAUSE, &mut |index, m| {
+                        match Self::CLAUSE[index] {
+                            ::ogma::clause::Token::Static(token) => m.next_word(token)?,
                             ::ogma::clause::Token::QueryVar(name) => match name {
                                 #(#query_var_matches)*
                                 _ => return Err(::ogma::matcher::MatchError::UnknownQueryVar),
                             },
-                            ::ogma::clause::Token::DataVar(name) => match name {
+                            ::ogma::clause::Token::DataVar(name, _) => match name {
                                 #(#data_var_matches)*
                                 _ => return Err(::ogma::matcher::MatchError::UnknownDataVar),
                             },
+                            ::ogma::clause::Token::TextVar(name) => match name {
+                                #(#text_var_matches)*
+                                _ => return Err(::ogma::matcher::MatchError::UnknownTextVar),
+                            },
+                            ::ogma::clause::Token::ListVar(name, _) => match name {
+                                #(#list_var_matches)*
+                                _ => return Err(::ogma::matcher::MatchError::UnknownListVar),
+                            },
+                            ::ogma::clause::Token::Optional(phrase) => {
+                                m.next_optional(phrase);
+                            },
+                            ::ogma::clause::Token::Alternatives(alternatives) => {
+                                m.next_alternative(alternatives)?;
+                            },
                         }
-                    }
-                    if m.is_empty() {
-                        Ok(#name {
-                            #(#var_assignments)*
-                        })
-                    } else {
-                        Err(::ogma::matcher::MatchError::ExpectedEof)
-                    }
+                        Ok(())
+                    })?;
+                    #block_match
+                    #matched
                 }
             }
         });
@@ -491,15 +710,84 @@ impl ToTokens for CallableImpl {
     }
 }
 
+/// Find the parameters of a function named by the variables of a clause
+fn find_vars(names: &[Ident], func_vars: &[FuncVar]) -> Result<Vec<FuncVar>, Error> {
+    names
+        .iter()
+        .map(|ident| {
+            func_vars
+                .iter()
+                .find(|var| &var.name == ident)
+                .cloned()
+                .ok_or_else(|| Error::new(ident.span(), "could not find variable in func"))
+        })
+        .collect()
+}
+
 fn check_var_names(desc: &Descriptor, func: &Func) -> Result<(), Error> {
     let possible_names = desc.parse_var_names()?;
-    let func_names = func.parse_var_names()?;
+    let mut func_names = func.parse_var_names()?;
+    if func.parse_block_var(desc)?.is_some() {
+        func_names.pop();
+    }
+    for var in &possible_names {
+        if !func_names.iter().any(|v1| v1 == var) {
+            return Err(Error::new(var.span(), "variable not found in func"));
+        }
+    }
+    for var in &func_names {
+        if !possible_names.iter().any(|v1| v1 == var) {
+            return Err(Error::new(var.span(), "variable not found in clause"));
+        }
+    }
     if possible_names.len() != func_names.len() {
         return Err(Error::new(desc.clause().span(), "variable number mismatch"));
     }
-    for var in func.parse_var_names()? {
-        if !possible_names.iter().any(|v1| v1 == &var) {
-            return Err(Error::new(var.span(), "variable not found in clause"));
+    Ok(())
+}
+
+fn check_var_types(desc: &Descriptor, func: &Func) -> Result<(), Error> {
+    let func_vars = func.parse_vars()?;
+    for (token, span) in desc.parse_clause_spanned()? {
+        let (name, ty) = match (token.var_name(), token.var_type()) {
+            (Some(name), Some(ty)) => (name, ty),
+            _ => continue,
+        };
+        let expected =
+            syn::parse_str::<Type>(ty).map_err(|_| Error::new(span, "invalid variable type"))?;
+        let var = match func_vars.iter().find(|var| var.name == name) {
+            Some(var) => var,
+            None => continue,
+        };
+        let found = if token.is_list_var() {
+            var.list_item_type()
+        } else {
+            Some(var.ty.as_ref())
+        };
+        if found.map(|ty| ty.to_token_stream().to_string())
+            != Some(expected.to_token_stream().to_string())
+        {
+            let msg = if token.is_list_var() {
+                format!("expected `Vec<{}>` from clause", ty)
+            } else {
+                format!("expected `{}` from clause", ty)
+            };
+            return Err(Error::new_spanned(&var.ty, msg));
+        }
+    }
+    Ok(())
+}
+
+fn check_text_vars(desc: &Descriptor) -> Result<(), Error> {
+    let tokens = desc.parse_clause_spanned()?;
+    for ((token, span), (next, _)) in tokens.iter().zip(tokens.iter().skip(1)) {
+        if (token.is_text_var() || token.is_list_var())
+            && !(next.is_static() || next.is_alternatives())
+        {
+            return Err(Error::new(
+                *span,
+                "text and list variables must be followed by a static token",
+            ));
         }
     }
     Ok(())
@@ -518,16 +806,18 @@ fn check_generics(func: &Func) -> Result<(), Error> {
 
 fn validate(desc: &Descriptor, func: &Func) -> Result<(), Error> {
     check_var_names(desc, func)?;
+    check_var_types(desc, func)?;
+    check_text_vars(desc)?;
     check_generics(func)?;
     Ok(())
 }
 
 pub fn ogma_fn(desc: &Descriptor, func: &Func, bdd: Option<Bdd>) -> Result<TokenStream, Error> {
-    validate(&desc, &func)?;
-    let fn_struct = Struct::build(&desc, &func)?;
-    let struct_impl = StructImpl::build(&desc, &func);
-    let match_impl = MatchImpl::build(&desc, &func, bdd)?;
-    let callable_impl = CallableImpl::build(&desc, &func)?;
+    validate(desc, func)?;
+    let fn_struct = Struct::build(desc, func)?;
+    let struct_impl = StructImpl::build(desc, func);
+    let match_impl = MatchImpl::build(desc, func, bdd)?;
+    let callable_impl = CallableImpl::build(desc, func)?;
     Ok(quote! {
         #fn_struct
         #struct_impl
@@ -535,3 +825,55 @@ pub fn ogma_fn(desc: &Descriptor, func: &Func, bdd: Option<Bdd>) -> Result<Token
         #callable_impl
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(clause: &str, func: &str) -> Result<(), String> {
+        let desc = syn::parse_str::<Descriptor>(&format!("Pay, {:?}", clause)).unwrap();
+        let func = syn::parse_str::<Func>(func).unwrap();
+        check_var_types(&desc, &func).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn var_types() {
+        let func = "fn pay(ctx: &mut Context, amount: i32) -> Result<(), Trap> { Ok(()) }";
+        assert!(check("pay d`amount:i32`", func).is_ok());
+        assert!(check("pay d`amount`", func).is_ok());
+        assert_eq!(
+            check("pay d`amount:u64`", func).unwrap_err(),
+            "expected `u64` from clause"
+        );
+        let func = "fn pay(ctx: &mut Context, amounts: Vec<i32>) -> Result<(), Trap> { Ok(()) }";
+        assert!(check("pay l`amounts:i32` now", func).is_ok());
+        assert_eq!(
+            check("pay l`amounts:u64` now", func).unwrap_err(),
+            "expected `Vec<u64>` from clause"
+        );
+    }
+
+    #[test]
+    fn var_parsers() {
+        let func = syn::parse_str::<Func>(
+            "fn pay(ctx: &mut Context, amount: i32) -> Result<(), Trap> { Ok(()) }",
+        )
+        .unwrap();
+        let desc = syn::parse_str::<Descriptor>(
+            "Pay, \"pay d`amount`\", priority = 1, parse(amount = parse_amount)",
+        )
+        .unwrap();
+        assert_eq!(desc.priority, Some(1));
+        assert!(MatchImpl::build(&desc, &func, None).is_ok());
+        let desc =
+            syn::parse_str::<Descriptor>("Pay, \"pay d`amount`\", parse(total = parse_amount)")
+                .unwrap();
+        assert_eq!(
+            MatchImpl::build(&desc, &func, None)
+                .err()
+                .unwrap()
+                .to_string(),
+            "expected a data or list variable of the clause"
+        );
+    }
+}
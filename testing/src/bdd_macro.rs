@@ -2,7 +2,8 @@ use crate::error::Fallible;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 use ogma::bdd;
-use ogma::module::{Module as ModuleTrait, ModuleList, ModuleType};
+use ogma::matcher::{Match, Matcher, Policy, Synonyms};
+use ogma::module::{Index, Module as ModuleTrait, ModuleList, ModuleType, WithPolicy};
 use ogma::object_query::Query;
 use ogma::vm::{Context, Trap};
 
@@ -40,6 +41,22 @@ fn sub<'a>(
     Ok(())
 }
 
+#[given(Plus, "q`input` (plus|added to|increased by) d`b` henceforth q`out`")]
+fn plus(
+    ctx: &mut Context,
+    input: &Vec<Query<'static>>,
+    b: i32,
+    out: &Vec<Query<'static>>,
+) -> Result<(), Trap> {
+    let input = input.iter().next().unwrap().as_key().unwrap();
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    let a = ctx
+        .get_global::<_, i32>(input)?
+        .ok_or_else(|| Trap::MissingGlobal(input.to_string()))?;
+    ctx.set_global::<_, i32>(out, a + b);
+    Ok(())
+}
+
 #[when(Equals, "q`left` is equal to q`right`")]
 fn equals(
     ctx: &mut Context,
@@ -66,10 +83,10 @@ fn noop(_: &mut Context) -> Result<(), Trap> {
     Ok(())
 }
 
-type Module<'a> = mod_type!(Add<'a>, Sub<'a>, Equals, Noop);
+type Module<'a> = mod_type!(Add<'a>, Sub<'a>, Plus, Equals, Noop);
 
 fn module<'a>() -> ModuleList<'a, bdd::Step> {
-    mod_list!(bdd::Step => Add, Sub, Equals, Noop)
+    mod_list!(bdd::Step => Add, Sub, Plus, Equals, Noop)
 }
 
 #[cfg_attr(feature = "std", test)]
@@ -98,6 +115,83 @@ fn test_given_add_extra_fail() -> Fallible<()> {
         r#"Given the addition of the input and 4 henceforth the output extra"#,
     )
     .is_err());
+    assert!(Add::match_str(&mut ctx, "Given the addition of the input and 4").is_err());
+    assert!(matches!(ctx, bdd::Step::Start));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_given_alternatives() -> Fallible<()> {
+    for line in &[
+        "Given the input plus 4 henceforth the output",
+        "Given the input added to 4 henceforth the output",
+        "Given the input increased by 4 henceforth the output",
+    ] {
+        let mut ctx = bdd::Step::new();
+        let script = Module::compile(&mut ctx, line).unwrap();
+        let mut instance = script.instance();
+        instance.ctx_mut().set_global::<_, i32>("input", 3);
+        instance.exec().unwrap();
+        let out = instance.ctx().get_global::<_, i32>("output").unwrap();
+        assert_eq!(out, Some(&7));
+    }
+    let mut ctx = bdd::Step::new();
+    assert!(Module::compile(&mut ctx, "Given the input minus 4 henceforth the output").is_err());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_lenient_policy() -> Fallible<()> {
+    let src = "given The addition of the input and 4 henceforth the left.\n\
+               AND the difference of the input and -4  henceforth the right!\n\
+               When the left is equal to the right\n\
+               then do nothing.";
+    let mut ctx = bdd::Step::new();
+    assert!(Module::compile(&mut ctx, src).is_err());
+    let mut ctx = bdd::Step::new();
+    let script = Module::compile_with(&mut ctx, src, &Policy::lenient()).unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 3);
+    instance.exec().unwrap();
+    let left = instance.ctx().get_global::<_, i32>("left").unwrap();
+    assert_eq!(left, Some(&7));
+    let right = instance.ctx().get_global::<_, i32>("right").unwrap();
+    assert_eq!(right, Some(&7));
+    let mut ctx = bdd::Step::new();
+    assert!(module()
+        .compile_with(&mut ctx, src, &Policy::lenient())
+        .is_ok());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_synonyms() -> Fallible<()> {
+    let synonyms = vec![vec!["addition", "sum", "total"], vec!["henceforth", "into"]]
+        .into_iter()
+        .collect::<Synonyms>();
+    let synonym_module = WithPolicy::new(module(), Policy::new().with_synonyms(synonyms));
+    let mut ctx = bdd::Step::new();
+    let script = synonym_module
+        .compile(
+            &mut ctx,
+            "Given the total of the input and 4 into the output",
+        )
+        .unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 3);
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, i32>("output").unwrap();
+    assert_eq!(out, Some(&7));
+    let mut ctx = bdd::Step::new();
+    assert!(module()
+        .compile(
+            &mut ctx,
+            "Given the total of the input and 4 into the output"
+        )
+        .is_err());
     Ok(())
 }
 
@@ -125,6 +219,32 @@ fn test_bdd() -> Fallible<()> {
     Ok(())
 }
 
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_bdd_asterisk() -> Fallible<()> {
+    let mut ctx = bdd::Step::new();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given the addition of the input and 4 henceforth the left
+        * the difference of the input and -4 henceforth the right
+        When the left is equal to the right
+        * the left is equal to the right
+        Then do nothing
+        * do nothing
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 3);
+    instance.exec().unwrap();
+    let right = instance.ctx().get_global::<_, i32>("right").unwrap();
+    assert_eq!(right, Some(&7));
+    let mut ctx = bdd::Step::new();
+    assert!(Module::compile(&mut ctx, "* do nothing").is_err());
+    Ok(())
+}
+
 #[cfg_attr(feature = "std", test)]
 #[cfg_attr(not(feature = "std"), test_case)]
 fn test_mod_list() -> Fallible<()> {
@@ -189,3 +309,62 @@ fn test_mod_list_err() -> Fallible<()> {
     assert_eq!(line_num, 4);
     Ok(())
 }
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_index() -> Fallible<()> {
+    let index = Index::new(Module::definitions());
+    let candidates = index
+        .candidates(&Matcher::new(
+            "Given the addition of the input and 4 henceforth the left",
+        ))
+        .into_iter()
+        .map(|definition| definition.clause)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        candidates,
+        [
+            <Add as Match<bdd::Step>>::clause(),
+            <Plus as Match<bdd::Step>>::clause()
+        ]
+    );
+    let candidates = index.candidates(&Matcher::new("Then do nothing"));
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].clause, <Noop as Match<bdd::Step>>::clause());
+    let mut ctx = bdd::Step::new();
+    let script = index
+        .compile(
+            &mut ctx,
+            r#"
+        Given the addition of the input and 4 henceforth the left
+        And the input plus 4 henceforth the right
+        When the left is equal to the right
+        Then do nothing
+        "#,
+        )
+        .unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 3);
+    instance.exec().unwrap();
+    let right = instance.ctx().get_global::<_, i32>("right").unwrap();
+    assert_eq!(right, Some(&7));
+    let mut ctx = bdd::Step::new();
+    let (line_num, err) = index
+        .compile(
+            &mut ctx,
+            "Given the addition of the input and 4 thence the left",
+        )
+        .err()
+        .unwrap();
+    assert_eq!(line_num, 0);
+    assert_eq!(err.suggestion(), Some(<Add as Match<bdd::Step>>::clause()));
+    let lenient = WithPolicy::new(Index::new(module().iter().copied()), Policy::lenient());
+    let mut ctx = bdd::Step::new();
+    assert!(lenient
+        .compile(
+            &mut ctx,
+            "given THE addition of the input and 4 henceforth the left."
+        )
+        .is_ok());
+    Ok(())
+}
@@ -3,7 +3,9 @@ use alloc::vec::Vec;
 use ogma::bdd;
 use ogma::clause::Token;
 use ogma::matcher::*;
+use ogma::module::ModuleType;
 use ogma::object_query::Query;
+use ogma::vm::{Callable, Context, Trap};
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Add<'a> {
@@ -24,32 +26,32 @@ impl<'a> Match<'a, bdd::Step> for Add<'a> {
         let mut b = None;
         let mut out = None;
         let mut matcher = Matcher::new(string);
-        let token = match matcher.next_static()? {
-            "Given" => "Given",
-            "And" => "And",
-            _ => return Err(::ogma::matcher::MatchError::MismatchedStaticToken),
-        };
+        let token = matcher.next_alternative("Given|And")?;
         if let Some(next_state) = ctx.next(token) {
             *ctx = next_state;
         } else {
-            return Err(::ogma::matcher::MatchError::InvalidCtx);
+            return Err(MatchError::InvalidCtx);
         }
         for token in &Self::CLAUSE {
             match *token {
-                Token::Static(token) => {
-                    if matcher.next_static()? != token {
-                        return Err(MatchError::MismatchedStaticToken);
-                    }
-                }
+                Token::Static(token) => matcher.next_word(token)?,
                 Token::QueryVar(name) => match name {
                     "out" => out = Some(matcher.next_query()?),
                     _ => return Err(MatchError::UnknownQueryVar),
                 },
-                Token::DataVar(name) => match name {
+                Token::DataVar(name, _) => match name {
                     "a" => a = Some(matcher.next_data()?),
                     "b" => b = Some(matcher.next_data()?),
                     _ => return Err(MatchError::UnknownDataVar),
                 },
+                Token::TextVar(_) => return Err(MatchError::UnknownTextVar),
+                Token::ListVar(..) => return Err(MatchError::UnknownListVar),
+                Token::Optional(phrase) => {
+                    matcher.next_optional(phrase);
+                }
+                Token::Alternatives(alternatives) => {
+                    matcher.next_alternative(alternatives)?;
+                }
             }
         }
         Ok(Add {
@@ -60,6 +62,12 @@ impl<'a> Match<'a, bdd::Step> for Add<'a> {
     }
 }
 
+impl<'a> Callable for Add<'a> {
+    fn call(&self, _: &mut Context) -> Result<(), Trap> {
+        Ok(())
+    }
+}
+
 #[cfg_attr(feature = "std", test)]
 #[cfg_attr(not(feature = "std"), test_case)]
 fn bdd_add_match() -> Fallible<()> {
@@ -78,3 +86,17 @@ fn bdd_add_match() -> Fallible<()> {
     );
     Ok(())
 }
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn bdd_rollback() -> Fallible<()> {
+    type Module<'a> = mod_type!(Add<'a>);
+    let mut ctx = bdd::Step::new();
+    assert!(Module::compile(&mut ctx, "Given the addition of 4 and 3 henceforth").is_err());
+    assert!(matches!(ctx, bdd::Step::Start));
+    assert!(Module::compile_checked(&mut ctx, "Given the addition of 4 and 3").is_err());
+    assert!(matches!(ctx, bdd::Step::Start));
+    assert!(Module::compile(&mut ctx, "Given the addition of 4 and 3 henceforth the sum").is_ok());
+    assert!(matches!(ctx, bdd::Step::Given));
+    Ok(())
+}
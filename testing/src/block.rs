@@ -0,0 +1,154 @@
+use crate::error::Fallible;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use ogma::block::{DataTable, DocString};
+use ogma::gherkin::Feature;
+use ogma::matcher::{Match, MatchError};
+use ogma::module::ModuleType;
+use ogma::object_query::Query;
+use ogma::vm::{Context, Trap};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Account<'a> {
+    #[allow(dead_code)]
+    name: &'a str,
+    balance: i32,
+}
+
+#[ogma_fn(Total, "Given the total of the accounts henceforth q`out`")]
+fn total<'a>(
+    ctx: &mut Context,
+    out: &Vec<Query<'a>>,
+    accounts: &DataTable<'a>,
+) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    let accounts = accounts
+        .deserialize_records::<Account>()
+        .map_err(|err| Trap::runtime(err.to_string()))?;
+    ctx.set_global::<_, i32>(out, accounts.iter().map(|account| account.balance).sum());
+    Ok(())
+}
+
+#[ogma_fn(Note, "Given the note henceforth q`out`")]
+fn note<'a>(
+    ctx: &mut Context,
+    out: &Vec<Query<'a>>,
+    note: &Option<DocString<'a>>,
+) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    let note = note.as_ref().map_or("", DocString::as_str);
+    ctx.set_global::<_, String>(out, note.to_string());
+    Ok(())
+}
+
+#[ogma_fn(Noop, "Given nothing")]
+fn noop(_: &mut Context) -> Result<(), Trap> {
+    Ok(())
+}
+
+type Module<'a> = mod_type!(Total<'a>, Note<'a>, Noop);
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_blocks() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given the total of the accounts henceforth the total
+          | name  | balance |
+          | Alice | 10      |
+          | Bob   | -3      |
+        Given the note henceforth the message
+          """
+          # not a comment
+
+          Hello
+          """
+        Given the note henceforth the empty
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&7));
+    let message = instance.ctx().get_global::<_, String>("message").unwrap();
+    assert_eq!(
+        message.map(String::as_str),
+        Some("# not a comment\n\nHello")
+    );
+    let empty = instance.ctx().get_global::<_, String>("empty").unwrap();
+    assert_eq!(empty.map(String::as_str), Some(""));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_block_errors() -> Fallible<()> {
+    let mut ctx = ();
+    assert!(matches!(
+        Total::match_str(
+            &mut ctx,
+            "Given the total of the accounts henceforth the total"
+        ),
+        Err(MatchError::MissingBlock)
+    ));
+    assert!(matches!(
+        Noop::match_str(&mut ctx, "Given nothing\n  | a |"),
+        Err(MatchError::UnexpectedBlock)
+    ));
+    assert!(matches!(
+        Total::match_str(
+            &mut ctx,
+            "Given the total of the accounts henceforth the total\n\"\"\"\n\"\"\""
+        ),
+        Err(MatchError::InvalidBlock)
+    ));
+    let (line_num, _) = Module::compile(
+        &mut ctx,
+        "Given nothing\n\
+         Given the note henceforth the message\n\
+         \"\"\"\n\
+         text\n\
+         \"\"\"\n\
+         Given nothing else",
+    )
+    .err()
+    .unwrap();
+    assert_eq!(line_num, 5);
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_feature_blocks() -> Fallible<()> {
+    let feature = Feature::parse(
+        r#"
+        Feature: Accounts
+
+          Scenario Outline: Totals
+            Given the total of the accounts henceforth the total
+              | name  | balance   |
+              | Alice | <balance> |
+            Given the note henceforth the message
+              """
+              Scenario: not a section
+              """
+
+            Examples:
+              | balance |
+              | 5       |
+        "#,
+    )
+    .unwrap();
+    let scripts = feature.compile_type::<Module, ()>().unwrap();
+    let mut instance = scripts[0].instance();
+    instance.exec().unwrap();
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&5));
+    let message = instance.ctx().get_global::<_, String>("message").unwrap();
+    assert_eq!(message.map(String::as_str), Some("Scenario: not a section"));
+    Ok(())
+}
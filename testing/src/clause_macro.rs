@@ -6,6 +6,12 @@ clause! { pub const CLAUSE1 = "the"; }
 clause! { pub(crate) const CLAUSE2 = "q`query_name`"; }
 clause! { const CLAUSE3 = "d`data_name`"; }
 clause! { const CLAUSE4 = "the q`query_name` and d`data_name` tokens"; }
+clause! { const CLAUSE5 = "[the] token [of the] q`query_name`"; }
+clause! { const CLAUSE6 = "d`data_name` (plus|added to) q`query_name`"; }
+clause! { const CLAUSE7 = "t`text_name`"; }
+clause! { const CLAUSE8 = "l`list_name`"; }
+clause! { const CLAUSE9 = "d`data_name:u64` l`list_name:String`"; }
+clause! { const CLAUSE10 = r"the \`quoted\` q`query_name`"; }
 
 #[cfg_attr(feature = "std", test)]
 #[cfg_attr(not(feature = "std"), test_case)]
@@ -31,7 +37,34 @@ fn query_token() -> Fallible<()> {
 #[cfg_attr(feature = "std", test)]
 #[cfg_attr(not(feature = "std"), test_case)]
 fn data_token() -> Fallible<()> {
-    assert_eq!(CLAUSE3, [Token::DataVar("data_name")]);
+    assert_eq!(CLAUSE3, [Token::DataVar("data_name", None)]);
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn text_token() -> Fallible<()> {
+    assert_eq!(CLAUSE7, [Token::TextVar("text_name")]);
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn list_token() -> Fallible<()> {
+    assert_eq!(CLAUSE8, [Token::ListVar("list_name", None)]);
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn typed_tokens() -> Fallible<()> {
+    assert_eq!(
+        CLAUSE9,
+        [
+            Token::DataVar("data_name", Some("u64")),
+            Token::ListVar("list_name", Some("String"))
+        ]
+    );
     Ok(())
 }
 
@@ -44,9 +77,52 @@ fn mixed_tokens() -> Fallible<()> {
             Token::Static("the"),
             Token::QueryVar("query_name"),
             Token::Static("and"),
-            Token::DataVar("data_name"),
+            Token::DataVar("data_name", None),
             Token::Static("tokens")
         ]
     );
     Ok(())
 }
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn optional_tokens() -> Fallible<()> {
+    assert_eq!(
+        CLAUSE5,
+        [
+            Token::Optional("the"),
+            Token::Static("token"),
+            Token::Optional("of the"),
+            Token::QueryVar("query_name")
+        ]
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn alternative_tokens() -> Fallible<()> {
+    assert_eq!(
+        CLAUSE6,
+        [
+            Token::DataVar("data_name", None),
+            Token::Alternatives("plus|added to"),
+            Token::QueryVar("query_name")
+        ]
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn escaped_tokens() -> Fallible<()> {
+    assert_eq!(
+        CLAUSE10,
+        [
+            Token::Static("the"),
+            Token::Static(r"\`quoted\`"),
+            Token::QueryVar("query_name")
+        ]
+    );
+    Ok(())
+}
@@ -1,12 +1,17 @@
 use crate::error::Fallible;
-use alloc::string::ToString;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use ogma::clause::Token;
+use ogma::matcher::{Match, MatchError, Matcher};
 use ogma::module::ModuleType;
 use ogma::object_query::Query;
 use ogma::vm::{Context, Trap};
+use serde::Deserialize;
 
-#[ogma_fn(#[derive(Debug)] Add, "Given the addition of q`input` and d`b` henceforth q`out`")]
-fn add<'a>(
+#[ogma_fn(#[derive(Debug)] Add, "Given the addition of q`input` and d`b:i32` henceforth q`out`")]
+pub(crate) fn add<'a>(
     ctx: &mut Context,
     input: &Vec<Query<'a>>,
     b: i32,
@@ -22,7 +27,100 @@ fn add<'a>(
     Ok(())
 }
 
-type Module<'a> = mod_type!(Add<'a>);
+#[ogma_fn(Mul, "Given [the] product of q`input` and d`b` [henceforth] q`out`")]
+pub(crate) fn mul<'a>(
+    ctx: &mut Context,
+    input: &Vec<Query<'a>>,
+    b: i32,
+    out: &Vec<Query<'a>>,
+) -> Result<(), Trap> {
+    let input = input.iter().next().unwrap().as_key().unwrap();
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    let a = ctx
+        .get_global::<_, i32>(input)?
+        .ok_or_else(|| Trap::MissingGlobal(input.to_string()))?;
+    let c = a * b;
+    ctx.set_global::<_, i32>(out, c);
+    Ok(())
+}
+
+#[ogma_fn(Log, "Given log t`message` henceforth q`out`")]
+#[allow(clippy::ptr_arg)]
+pub(crate) fn log<'a>(
+    ctx: &mut Context,
+    message: &Cow<'a, str>,
+    out: &Vec<Query<'a>>,
+) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, String>(out, message.to_string());
+    Ok(())
+}
+
+#[ogma_fn(Say, "Given say t`message`")]
+#[allow(clippy::ptr_arg)]
+pub(crate) fn say(ctx: &mut Context, message: &String) -> Result<(), Trap> {
+    ctx.set_global::<_, String>("said", message.clone());
+    Ok(())
+}
+
+#[ogma_fn(Sum, "Given the sum of l`items:i32` henceforth q`out`")]
+#[allow(clippy::ptr_arg)]
+pub(crate) fn sum<'a>(
+    ctx: &mut Context,
+    items: &Vec<i32>,
+    out: &Vec<Query<'a>>,
+) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, i32>(out, items.iter().sum());
+    Ok(())
+}
+
+#[ogma_fn(Path, "Given the path to q`field` of q`record` henceforth q`out`")]
+pub(crate) fn path<'a>(
+    ctx: &mut Context,
+    field: &Vec<Query<'a>>,
+    record: &Vec<Query<'a>>,
+    out: &Vec<Query<'a>>,
+) -> Result<(), Trap> {
+    let field = field.iter().next().unwrap().as_key().unwrap();
+    let record = record.iter().next().unwrap().as_key().unwrap();
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, String>(out, format!("{}.{}", record, field));
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Percent(u32);
+
+/// Read a percentage such as "15%", which NLSD does not understand
+fn percent(matcher: &mut Matcher) -> Result<Percent, MatchError> {
+    matcher
+        .next_static()?
+        .strip_suffix('%')
+        .and_then(|percent| percent.parse().ok())
+        .map(Percent)
+        .ok_or(MatchError::InvalidData)
+}
+
+#[ogma_fn(
+    Discount,
+    "Given a discount of d`rate:Percent` on top of l`extra:Percent` henceforth q`out`",
+    parse(rate = percent, extra = percent)
+)]
+#[allow(clippy::ptr_arg)]
+fn discount<'a>(
+    ctx: &mut Context,
+    rate: &Percent,
+    extra: &Vec<Percent>,
+    out: &Vec<Query<'a>>,
+) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    let total = rate.0 + extra.iter().map(|percent| percent.0).sum::<u32>();
+    ctx.set_global::<_, u32>(out, total);
+    Ok(())
+}
+
+pub(crate) type Module<'a> = mod_type!(Add<'a>, Mul<'a>, Log<'a>, Say, Sum<'a>, Path<'a>);
 
 #[cfg_attr(feature = "std", test)]
 #[cfg_attr(not(feature = "std"), test_case)]
@@ -40,3 +138,142 @@ fn test_add() -> Fallible<()> {
     assert_eq!(out, Some(&7));
     Ok(())
 }
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_optional_words() -> Fallible<()> {
+    let mut ctx = ();
+    for line in &[
+        "Given the product of the input and 4 henceforth the output",
+        "Given product of the input and 4 the output",
+    ] {
+        let script = Module::compile(&mut ctx, line).unwrap();
+        let mut instance = script.instance();
+        instance.ctx_mut().set_global::<_, i32>("input", 3);
+        instance.exec().unwrap();
+        let out = instance.ctx().get_global::<_, i32>("output").unwrap();
+        assert_eq!(out, Some(&12));
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_text_vars() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given log the sum is 4, or 5 henceforth the output
+        Given say hello world
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, String>("output").unwrap();
+    assert_eq!(out.map(String::as_str), Some("the sum is 4, or 5"));
+    let said = instance.ctx().get_global::<_, String>("said").unwrap();
+    assert_eq!(said.map(String::as_str), Some("hello world"));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_list_vars() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given the sum of 1, 2 and 3 henceforth the total
+        Given the sum of 4 henceforth the single
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&6));
+    let single = instance.ctx().get_global::<_, i32>("single").unwrap();
+    assert_eq!(single, Some(&4));
+    assert!(Module::compile(
+        &mut ctx,
+        "Given the sum of 1 and 2 or 3 henceforth the total"
+    )
+    .is_err());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_var_types() -> Fallible<()> {
+    let types = Add::CLAUSE
+        .iter()
+        .filter_map(|token| Some((token.var_name()?, token.var_type())))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        vec![("input", None), ("b", Some("i32")), ("out", None)]
+    );
+    assert_eq!(Sum::CLAUSE[4], Token::ListVar("items", Some("i32")));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_adjacent_queries() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        "Given the path to the name of the user henceforth the output",
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, String>("output").unwrap();
+    assert_eq!(out.map(String::as_str), Some("user.name"));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_mismatch() -> Fallible<()> {
+    let mut ctx = ();
+    let err = Add::match_str(
+        &mut ctx,
+        "Given the addition of the input and 4 then the output",
+    )
+    .unwrap_err();
+    match err {
+        MatchError::MismatchedStaticToken(ref mismatch) => {
+            assert_eq!(mismatch.column, 39);
+            assert_eq!(mismatch.expected, "henceforth");
+            assert_eq!(mismatch.found, "then");
+        }
+        _ => panic!("unexpected error {}", err),
+    }
+    let err = Add::match_str(&mut ctx, "Given the addition of the input and 4").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected 'henceforth' at column 38, found end of line"
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_var_parser() -> Fallible<()> {
+    let mut ctx = ();
+    let discount = Discount::match_str(
+        &mut ctx,
+        "Given a discount of 15% on top of 5% and 2% henceforth the output",
+    )?;
+    assert_eq!(discount.rate, Percent(15));
+    assert_eq!(discount.extra, [Percent(5), Percent(2)]);
+    assert!(Discount::match_str(
+        &mut ctx,
+        "Given a discount of 15 on top of 5% henceforth the output"
+    )
+    .is_err());
+    Ok(())
+}
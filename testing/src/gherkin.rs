@@ -0,0 +1,201 @@
+use crate::error::Fallible;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use ogma::bdd;
+use ogma::gherkin::Feature;
+use ogma::matcher::MatchError;
+use ogma::module::ModuleList;
+use ogma::object_query::Query;
+use ogma::tags::TagExpression;
+use ogma::vm::{Context, Trap};
+
+#[given(Set, "q`out` is d`value`")]
+fn set<'a>(ctx: &mut Context, out: &Vec<Query<'a>>, value: i32) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, i32>(out, value);
+    Ok(())
+}
+
+#[when(Add, "d`b` is added to q`input`")]
+fn add<'a>(ctx: &mut Context, b: i32, input: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let input = input.iter().next().unwrap().as_key().unwrap();
+    let a = ctx
+        .get_global::<_, i32>(input)?
+        .ok_or_else(|| Trap::MissingGlobal(input.to_string()))?;
+    ctx.set_global::<_, i32>(input, a + b);
+    Ok(())
+}
+
+#[then(Equals, "q`input` is equal to d`expected`")]
+fn equals<'a>(ctx: &mut Context, input: &Vec<Query<'a>>, expected: i32) -> Result<(), Trap> {
+    let input = input.iter().next().unwrap().as_key().unwrap();
+    let a = ctx
+        .get_global::<_, i32>(input)?
+        .ok_or_else(|| Trap::MissingGlobal(input.to_string()))?;
+    if *a != expected {
+        Err(Trap::runtime("not equal"))
+    } else {
+        Ok(())
+    }
+}
+
+type Module<'a> = mod_type!(Set<'a>, Add<'a>, Equals<'a>);
+
+fn module<'a>() -> ModuleList<'a, bdd::Step> {
+    mod_list!(bdd::Step => Set, Add, Equals)
+}
+
+const FEATURE: &str = r#"
+Feature: Addition
+  Numbers can be added to the total
+
+  Background:
+    Given the total is 1
+
+  Scenario: Add once
+    When 2 is added to the total
+    Then the total is equal to 3
+
+  Scenario Outline: Add <a> and <b>
+    Given the other is 0
+    When <a> is added to the total
+    And <b> is added to the total
+    Then the total is equal to <sum>
+    But the other is equal to 0
+
+    Examples:
+      | a | b  | sum |
+      | 1 | 2  | 4   |
+      | 5 | -5 | 1   |
+"#;
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_feature() -> Fallible<()> {
+    let feature = Feature::parse(FEATURE).unwrap();
+    assert_eq!(feature.name, "Addition");
+    let scripts = feature.compile_type::<Module, bdd::Step>().unwrap();
+    let titles = scripts
+        .iter()
+        .map(|script| script.metadata().title.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["Add once", "Add 1 and 2", "Add 5 and -5"]);
+    for script in &scripts {
+        let mut instance = script.instance();
+        instance.exec().unwrap();
+    }
+    let scripts = feature.compile(&module()).unwrap();
+    assert_eq!(scripts.len(), 3);
+    for script in &scripts {
+        let mut instance = script.instance();
+        instance.exec().unwrap();
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_failing_scenario() -> Fallible<()> {
+    let src = FEATURE.replace("| 1   |", "| 2   |");
+    let feature = Feature::parse(&src).unwrap();
+    let scripts = feature.compile_type::<Module, bdd::Step>().unwrap();
+    assert!(scripts[1].instance().exec().is_ok());
+    assert!(scripts[2].instance().exec().is_err());
+    let src = FEATURE.replace("And <b> is added", "And <b> is multiplied");
+    let feature = Feature::parse(&src).unwrap();
+    let mut ctx = bdd::Step::new();
+    assert!(feature.scenarios[0].compile(&module(), &mut ctx).is_ok());
+    let mut ctx = bdd::Step::new();
+    let (line_num, err) = feature.scenarios[1]
+        .compile(&module(), &mut ctx)
+        .err()
+        .unwrap();
+    assert_eq!(line_num, 14);
+    assert!(matches!(err, MatchError::NoMatch(_)));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_invalid_feature() -> Fallible<()> {
+    let (line_num, err) = Feature::parse("Feature: Addition\n  Examples:")
+        .err()
+        .unwrap();
+    assert_eq!(line_num, 1);
+    assert_eq!(
+        err.to_string(),
+        "invalid feature: expected 'Scenario:' at column 1, found 'Examples:'"
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_tags() -> Fallible<()> {
+    let src = r#"
+@arithmetic
+Feature: Tagged addition
+
+  @smoke
+  Scenario: Add once
+    Given the total is 1
+    When 2 is added to the total
+    Then the total is equal to 3
+
+  @slow @wip # still being written
+  Scenario Outline: Add <a>
+    Given the total is 0
+    When <a> is added to the total
+    Then the total is equal to <a>
+
+    @smoke
+    Examples:
+      | a |
+      | 1 |
+
+    Examples:
+      | a |
+      | 2 |
+"#;
+    let feature = Feature::parse(src).unwrap();
+    assert_eq!(feature.tags, ["@arithmetic"]);
+    let scripts = feature.compile(&module()).unwrap();
+    let tags = scripts
+        .iter()
+        .map(|script| script.metadata().tags.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tags,
+        [
+            vec!["@arithmetic", "@smoke"],
+            vec!["@arithmetic", "@slow", "@wip", "@smoke"],
+            vec!["@arithmetic", "@slow", "@wip"],
+        ]
+    );
+    let expression = TagExpression::parse("@smoke and not @slow").unwrap();
+    let selected = scripts
+        .iter()
+        .filter(|script| expression.matches(&script.metadata().tags))
+        .map(|script| script.metadata().title.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(selected, ["Add once"]);
+    let mut feature = Feature::parse(src).unwrap();
+    feature.retain(&TagExpression::parse("@slow").unwrap());
+    let scripts = feature.compile(&module()).unwrap();
+    assert_eq!(scripts.len(), 2);
+    for script in &scripts {
+        script.instance().exec().unwrap();
+    }
+    let (line_num, err) = Feature::parse("Feature: A\n  @smoke\n  Given the total is 1")
+        .err()
+        .unwrap();
+    assert_eq!(line_num, 2);
+    assert!(matches!(err, MatchError::InvalidFeature(_)));
+    let (line_num, err) = Feature::parse("@smoke slow\nFeature: A").err().unwrap();
+    assert_eq!(line_num, 0);
+    assert_eq!(
+        err.to_string(),
+        "invalid feature: expected '@tag' at column 8, found 'slow'"
+    );
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use crate::error::Fallible;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use ogma::include::{MemoryResolver, WithResolver};
+use ogma::matcher::MatchError;
+use ogma::module::{Module, ModuleList};
+use ogma::object_query::Query;
+use ogma::phrase::PhraseScript;
+use ogma::vm::{Context, Trap};
+
+#[ogma_fn(Set, "Given the value d`value` henceforth q`out`")]
+fn set<'a>(ctx: &mut Context, value: i32, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, i32>(out, value);
+    Ok(())
+}
+
+#[ogma_fn(Add, "Add d`b` to q`input`")]
+fn add<'a>(ctx: &mut Context, b: i32, input: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let input = input.iter().next().unwrap().as_key().unwrap();
+    let a = ctx
+        .get_global::<_, i32>(input)?
+        .ok_or_else(|| Trap::MissingGlobal(input.to_string()))?;
+    ctx.set_global::<_, i32>(input, a + b);
+    Ok(())
+}
+
+fn module<'a>() -> ModuleList<'a, ()> {
+    mod_list!(() => Set, Add)
+}
+
+fn resolver() -> MemoryResolver {
+    vec![
+        (
+            "setup",
+            "Given the value 1 henceforth the total\nInclude the steps from `shared/add`",
+        ),
+        ("shared/add", "Add 2 to the total\n\nAdd 3 to the total"),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_include() -> Fallible<()> {
+    let resolver = resolver();
+    let module = WithResolver::new(module(), &resolver);
+    let script = module
+        .compile(
+            &mut (),
+            r#"
+        ---
+        title: Included setup
+        ---
+        Include the steps from `setup`
+        Add 4 to the total
+        "#,
+        )
+        .unwrap();
+    assert_eq!(script.metadata().title, Some("Included setup"));
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&10));
+    assert!(module
+        .compile_checked(&mut (), "Include the steps from `setup`")
+        .is_ok());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_include_errors() -> Fallible<()> {
+    let mut resolver = resolver();
+    resolver.insert(
+        "shared/add",
+        "Add 2 to the total\n\nAdd plenty to the total",
+    );
+    let included = WithResolver::new(module(), &resolver);
+    let src = "Add 1 to the total\nInclude the steps from `setup`";
+    let (line_num, err) = included.compile(&mut (), src).err().unwrap();
+    assert_eq!(line_num, 1);
+    match &err {
+        MatchError::Included(name, line, err) => {
+            assert_eq!(name, "setup");
+            assert_eq!(*line, 1);
+            assert!(matches!(&**err, MatchError::Included(name, 2, _) if name == "shared/add"));
+        }
+        _ => panic!("expected an error in an included script"),
+    }
+    let errors = included.compile_checked(&mut (), src).err().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, 1);
+    assert!(errors[0]
+        .1
+        .to_string()
+        .starts_with("in 'setup' at line 2: in 'shared/add' at line 3: "));
+    resolver.insert("shared/add", "Include the steps from `setup`");
+    let cyclic = WithResolver::new(module(), &resolver);
+    let (_, err) = cyclic.compile(&mut (), src).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "in 'setup' at line 2: in 'shared/add' at line 1: script 'setup' includes itself"
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_include_in_phrases() -> Fallible<()> {
+    let script = PhraseScript::parse(
+        r#"
+        To prepare q`account`:
+            Include the steps from `setup`
+            Add 4 to <account>
+        Prepare the total
+        "#,
+    )
+    .unwrap();
+    let missing = PhraseScript::parse("Include the steps from `missing`").unwrap();
+    let resolver = resolver();
+    let module = WithResolver::new(module(), &resolver);
+    let compiled = script.compile(&module, &mut ()).unwrap();
+    let mut instance = compiled.instance();
+    instance.exec().unwrap();
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&10));
+    let (_, err) = missing.compile(&module, &mut ()).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "could not find the included script 'missing'"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_file_resolver() -> Fallible<()> {
+    use ogma::include::FileResolver;
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("ogma-include-{}", std::process::id()));
+    fs::create_dir_all(root.join("shared"))?;
+    fs::write(
+        root.join("setup.ogma"),
+        "Given the value 1 henceforth the total\nInclude the steps from `shared/add.ogma`",
+    )?;
+    let resolver = FileResolver::new(&root)?;
+    fs::write(root.join("shared").join("add.ogma"), "Add 2 to the total")?;
+    fs::write(root.join("invalid.ogma"), [0xff, 0xfe])?;
+    let module = WithResolver::new(module(), &resolver);
+    let script = module
+        .compile(&mut (), "Include the steps from `setup.ogma`")
+        .unwrap();
+    let error = |src| module.compile(&mut (), src).err().unwrap().1.to_string();
+    let missing = error("Include the steps from `missing.ogma`");
+    let outside = error("Include the steps from `shared/../../setup.ogma`");
+    let invalid = error("Include the steps from `invalid.ogma`");
+    fs::remove_dir_all(&root)?;
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&3));
+    assert_eq!(missing, "could not find the included script 'missing.ogma'");
+    assert_eq!(
+        outside,
+        "could not find the included script 'shared/../../setup.ogma'"
+    );
+    assert!(invalid.starts_with("could not read the included script 'invalid.ogma': "));
+    Ok(())
+}
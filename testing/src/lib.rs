@@ -25,8 +25,24 @@ mod bdd_macro;
 #[cfg(test)]
 mod bdd_matcher;
 #[cfg(test)]
+mod block;
+#[cfg(test)]
 mod clause_macro;
 #[cfg(test)]
 mod fn_macro;
 #[cfg(test)]
+mod gherkin;
+#[cfg(test)]
+mod include;
+#[cfg(test)]
 mod matcher;
+#[cfg(test)]
+mod module;
+#[cfg(test)]
+mod number;
+#[cfg(test)]
+mod phrase;
+#[cfg(test)]
+mod quantity;
+#[cfg(test)]
+mod time;
@@ -2,7 +2,9 @@ use crate::error::Fallible;
 use alloc::vec::Vec;
 use ogma::clause::Token;
 use ogma::matcher::*;
+use ogma::module::Module;
 use ogma::object_query::Query;
+use ogma::vm::{Context, Trap};
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Add<'a> {
@@ -25,20 +27,24 @@ impl<'a, C> Match<'a, C> for Add<'a> {
         let mut matcher = Matcher::new(string);
         for token in &Self::CLAUSE {
             match *token {
-                Token::Static(token) => {
-                    if matcher.next_static()? != token {
-                        return Err(MatchError::MismatchedStaticToken);
-                    }
-                }
+                Token::Static(token) => matcher.next_word(token)?,
                 Token::QueryVar(name) => match name {
                     "out" => out = Some(matcher.next_query()?),
                     _ => return Err(MatchError::UnknownQueryVar),
                 },
-                Token::DataVar(name) => match name {
+                Token::DataVar(name, _) => match name {
                     "a" => a = Some(matcher.next_data()?),
                     "b" => b = Some(matcher.next_data()?),
                     _ => return Err(MatchError::UnknownDataVar),
                 },
+                Token::TextVar(_) => return Err(MatchError::UnknownTextVar),
+                Token::ListVar(..) => return Err(MatchError::UnknownListVar),
+                Token::Optional(phrase) => {
+                    matcher.next_optional(phrase);
+                }
+                Token::Alternatives(alternatives) => {
+                    matcher.next_alternative(alternatives)?;
+                }
             }
         }
         Ok(Add {
@@ -67,3 +73,56 @@ fn add_match() -> Fallible<()> {
     );
     Ok(())
 }
+
+#[derive(Debug, PartialEq)]
+struct Money {
+    amount: u64,
+    currency: String,
+}
+
+impl<'a> MatchData<'a> for Money {
+    fn match_data(matcher: &mut Matcher<'a>) -> Result<Self, MatchError> {
+        let amount = matcher.next_data()?;
+        let currency = matcher.next_static()?;
+        if currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase()) {
+            Ok(Money {
+                amount,
+                currency: currency.to_string(),
+            })
+        } else {
+            Err(MatchError::InvalidData)
+        }
+    }
+}
+
+#[ogma_fn(Pay, "Given a payment of d`price:Money` henceforth q`out`")]
+fn pay<'a>(ctx: &mut Context, price: &Money, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, String>(out, format!("{} {}", price.amount, price.currency));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_match_data() -> Fallible<()> {
+    let mut ctx = ();
+    let module = mod_list!(() => Pay);
+    let script = module
+        .compile(&mut ctx, "Given a payment of 5 EUR henceforth the output")
+        .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, String>("output").unwrap();
+    assert_eq!(out.map(String::as_str), Some("5 EUR"));
+    let (_, err) = module
+        .compile(&mut ctx, "Given a payment of 5 euros henceforth the output")
+        .err()
+        .unwrap();
+    match err {
+        MatchError::NoMatch(ref candidates) => {
+            assert!(matches!(candidates[0].error, MatchError::InvalidVar(_, _)));
+        }
+        _ => panic!("unexpected error {}", err),
+    }
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use crate::error::Fallible;
+use crate::fn_macro::{Add, Module, Mul};
+use alloc::string::String;
+use alloc::vec::Vec;
+use ogma::matcher::{Match, MatchError, Matcher};
+use ogma::module::{Index, Module as ModuleTrait, ModuleType};
+use ogma::object_query::Query;
+use ogma::vm::{Context, Func, Trap};
+
+#[ogma_fn(
+    Double,
+    "Given the addition of q`input` and 2 henceforth q`out`",
+    priority = 1
+)]
+fn double<'a>(ctx: &mut Context, input: &Vec<Query<'a>>, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    Mul::mul(ctx, input, 2, out)
+}
+
+#[ogma_fn(Increment, "Given the addition of q`input` and 1 henceforth q`out`")]
+fn increment<'a>(
+    ctx: &mut Context,
+    input: &Vec<Query<'a>>,
+    out: &Vec<Query<'a>>,
+) -> Result<(), Trap> {
+    Add::add(ctx, input, 1, out)
+}
+
+#[ogma_fn(Assign, "q`input` is assigned to q`out`")]
+fn assign<'a>(ctx: &mut Context, input: &Vec<Query<'a>>, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    Add::add(ctx, input, 0, out)
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_no_match() -> Fallible<()> {
+    let mut ctx = ();
+    let (line, err) = Module::compile(
+        &mut ctx,
+        "Given the addition of the input and 4 then the output",
+    )
+    .err()
+    .unwrap();
+    assert_eq!(line, 0);
+    assert_eq!(err.suggestion(), Some(<Add as Match<()>>::clause()));
+    match err {
+        MatchError::NoMatch(ref candidates) => {
+            assert_eq!(candidates.len(), 6);
+            assert_eq!(candidates[0].clause, <Add as Match<()>>::clause());
+            assert_eq!(candidates[0].offset(), 38);
+        }
+        _ => panic!("unexpected error {}", err),
+    }
+    assert!(err.to_string().starts_with(
+        "no step matched, did you mean: Given the addition of … and … henceforth …?\n  \
+         Given the addition of … and … henceforth …: expected 'henceforth' at column 39, found 'then'"
+    ));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_priority() -> Fallible<()> {
+    type Overlapping<'a> = mod_type!(Add<'a>, Double<'a>);
+    let mut ctx = ();
+    let line = "Given the addition of the input and 2 henceforth the output";
+    for script in [
+        Overlapping::compile(&mut ctx, line).unwrap(),
+        Overlapping::compile_checked(&mut ctx, line).unwrap(),
+    ] {
+        let mut instance = script.instance();
+        instance.ctx_mut().set_global::<_, i32>("input", 5);
+        instance.exec().unwrap();
+        let out = instance.ctx().get_global::<_, i32>("output").unwrap();
+        assert_eq!(out, Some(&10));
+    }
+    Ok(())
+}
+
+/// A match context which opts into `MatchContext` through its `Clone` impl
+#[derive(Clone)]
+struct Cloned;
+
+match_context!(Cloned);
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_match_contexts() -> Fallible<()> {
+    type Overlapping<'a> = mod_type!(Add<'a>, Double<'a>);
+    let line = "Given the addition of the input and 2 henceforth the output";
+    assert!(Overlapping::compile(&mut Cloned, line).is_ok());
+    assert!(mod_list!(Cloned => Add, Double)
+        .compile(&mut Cloned, line)
+        .is_ok());
+    assert!(Overlapping::compile_checked(&mut Cloned, line).is_ok());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_unindexed() -> Fallible<()> {
+    let index = Index::new(mod_list!(() => Add, Assign).iter().copied());
+    let line = "Given the addition of the input and 2 henceforth the output";
+    assert_eq!(index.candidates(&Matcher::new(line)).len(), 2);
+    let candidates = index.candidates(&Matcher::new("the input is assigned to the output"));
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].clause, &Assign::CLAUSE[..]);
+    let script = index
+        .compile(
+            &mut (),
+            r#"
+            the input is assigned to the left
+            Given the addition of the left and 2 henceforth the output
+            "#,
+        )
+        .unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 5);
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, i32>("output").unwrap();
+    assert_eq!(out, Some(&7));
+    Ok(())
+}
+
+/// A module which does not list its functions
+struct Unlisted;
+
+impl<'a> ModuleTrait<'a, ()> for Unlisted {
+    type Error = MatchError;
+    fn compile_matcher(&self, ctx: &mut (), matcher: Matcher<'a>) -> Result<Func<'a>, MatchError> {
+        mod_list!(() => Add, Double).compile_matcher(ctx, matcher)
+    }
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_unlisted_module() -> Fallible<()> {
+    let line = "Given the addition of the input and 2 henceforth the output";
+    assert!(Unlisted.compile_checked(&mut (), line).is_ok());
+    let errors = Unlisted
+        .compile_checked(&mut (), "Given the addition of the input")
+        .err()
+        .unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0], (0, MatchError::NoMatch(_))));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_ambiguous() -> Fallible<()> {
+    let module = mod_list!(() => Add, Increment);
+    let mut ctx = ();
+    let errors = module
+        .compile_checked(
+            &mut ctx,
+            r#"
+            Given the addition of the input and 2 henceforth the output
+            Given the addition of the input and 1 henceforth the output
+            Given the sum of 4 henceforth the output
+            "#,
+        )
+        .err()
+        .unwrap();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].0, 2);
+    match errors[0].1 {
+        MatchError::Ambiguous(ref clauses) => {
+            assert_eq!(
+                clauses,
+                &[<Add as Match<()>>::clause(), &Increment::CLAUSE[..]]
+            );
+        }
+        ref err => panic!("unexpected error {}", err),
+    }
+    assert_eq!(errors[1].0, 3);
+    assert!(matches!(errors[1].1, MatchError::NoMatch(_)));
+    assert!(module
+        .compile(
+            &mut ctx,
+            "Given the addition of the input and 1 henceforth the output"
+        )
+        .is_ok());
+    assert_eq!(module.definitions()[1].clause, &Increment::CLAUSE[..]);
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_comments_and_header() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        ---
+        title: Payments
+        # the version is bumped on every change
+        version: 1.2
+        author: Jane Doe
+        tags: @payments @smoke
+        ---
+        # start from the input
+        Given the addition of the input and 4 henceforth the left # a trailing comment
+        Given log `# not a comment` henceforth the message
+        "#,
+    )
+    .unwrap();
+    let metadata = script.metadata();
+    assert_eq!(metadata.title, Some("Payments"));
+    assert_eq!(metadata.version, Some("1.2"));
+    assert_eq!(metadata.author, Some("Jane Doe"));
+    assert_eq!(metadata.description, None);
+    assert_eq!(metadata.tags, ["@payments", "@smoke"]);
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 3);
+    instance.exec().unwrap();
+    let left = instance.ctx().get_global::<_, i32>("left").unwrap();
+    assert_eq!(left, Some(&7));
+    let message = instance.ctx().get_global::<_, String>("message").unwrap();
+    assert_eq!(message.map(String::as_str), Some("`# not a comment`"));
+    Ok(())
+}
+
+/// A module which only implements `compile_line`
+struct Legacy;
+
+impl<'a> ModuleTrait<'a, ()> for Legacy {
+    type Error = MatchError;
+    fn compile_line(&self, ctx: &mut (), string: &'a str) -> Result<Func<'a>, MatchError> {
+        Module::compile_line(ctx, string)
+    }
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_legacy_module() -> Fallible<()> {
+    let line = "Given the addition of the input and 2 henceforth the output";
+    let script = Legacy.compile(&mut (), line).unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 5);
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, i32>("output").unwrap();
+    assert_eq!(out, Some(&7));
+    assert!(Legacy.compile_checked(&mut (), line).is_ok());
+    Ok(())
+}
+
+/// A module whose errors are not `MatchError`s
+struct Strict;
+
+impl<'a> ModuleTrait<'a, ()> for Strict {
+    type Error = &'static str;
+    fn compile_matcher(&self, ctx: &mut (), matcher: Matcher<'a>) -> Result<Func<'a>, Self::Error> {
+        mod_list!(() => Add)
+            .compile_matcher(ctx, matcher)
+            .map_err(|_| "no step matched")
+    }
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_custom_error() -> Fallible<()> {
+    let script = Strict
+        .compile(
+            &mut (),
+            "---\ntitle: Payments\n---\nGiven the addition of the input and 2 henceforth the output",
+        )
+        .unwrap();
+    assert_eq!(script.metadata().title, Some("Payments"));
+    let err = Strict
+        .compile(&mut (), "---\nowner: Jane\n---")
+        .err()
+        .unwrap();
+    assert_eq!(err, (0, "no step matched"));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_invalid_header() -> Fallible<()> {
+    let mut ctx = ();
+    let (line, err) = Module::compile(&mut ctx, "---\nowner: Jane\n---")
+        .err()
+        .unwrap();
+    assert_eq!(line, 0);
+    assert!(matches!(err, MatchError::NoMatch(_)));
+    let errors = Module::compile_checked(&mut ctx, "---\nowner: Jane\n---")
+        .err()
+        .unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, 1);
+    assert_eq!(
+        errors[0].1.to_string(),
+        "invalid script header: expected 'title, version, author, description or tags' at column 1, \
+         found 'owner:'"
+    );
+    let errors = Module::compile_checked(&mut ctx, "---\ntitle: Payments")
+        .err()
+        .unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, 0);
+    assert!(matches!(errors[0].1, MatchError::InvalidHeader(_)));
+    let errors = Module::compile_checked(&mut ctx, "---\ntags: @payments smoke\n---")
+        .err()
+        .unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, 1);
+    assert_eq!(
+        errors[0].1.to_string(),
+        "invalid script header: expected '@tag' at column 17, found 'smoke'"
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_continued_steps() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given the addition of the input \
+            and 4 \
+            henceforth the left
+        Given the addition of the \
+            input and 1 henceforth the right
+        Given the sum of \
+            1, 2 and \
+            3 henceforth the total # a trailing comment
+        Given log hello \
+            world henceforth the greeting
+            Given log `indented after a comment` henceforth the message
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 3);
+    instance.exec().unwrap();
+    let left = instance.ctx().get_global::<_, i32>("left").unwrap();
+    assert_eq!(left, Some(&7));
+    let right = instance.ctx().get_global::<_, i32>("right").unwrap();
+    assert_eq!(right, Some(&4));
+    let greeting = instance.ctx().get_global::<_, String>("greeting").unwrap();
+    assert_eq!(greeting.map(String::as_str), Some("hello world"));
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&6));
+    let message = instance.ctx().get_global::<_, String>("message").unwrap();
+    assert_eq!(
+        message.map(String::as_str),
+        Some("`indented after a comment`")
+    );
+    let (line, err) = Module::compile(
+        &mut ctx,
+        "Given log `first` henceforth the message\n\
+         Given the addition of the input \\\n  \
+           and plenty \\\n  \
+           henceforth the left",
+    )
+    .err()
+    .unwrap();
+    assert_eq!(line, 1);
+    match err {
+        MatchError::NoMatch(ref candidates) => match candidates[0].error.mismatch() {
+            Some(mismatch) => {
+                assert_eq!(mismatch.line, 1);
+                assert_eq!(mismatch.column, 5);
+            }
+            None => panic!("unexpected error {}", err),
+        },
+        _ => panic!("unexpected error {}", err),
+    }
+    assert!(err.to_string().contains("at line 2 of the step, column 5"));
+    let script = Module::compile(
+        &mut ctx,
+        "Given log `first` henceforth the first\n    Given log `second` henceforth the second",
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let second = instance.ctx().get_global::<_, String>("second").unwrap();
+    assert_eq!(second.map(String::as_str), Some("`second`"));
+    Ok(())
+}
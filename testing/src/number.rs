@@ -0,0 +1,56 @@
+use crate::error::Fallible;
+use crate::fn_macro::Module;
+use alloc::vec::Vec;
+use ogma::module::ModuleType;
+use ogma::object_query::Query;
+use ogma::vm::{Context, Trap};
+
+#[ogma_fn(
+    Between,
+    "Given the numbers between d`low` and d`high` henceforth q`out`"
+)]
+fn between<'a>(ctx: &mut Context, low: i32, high: i32, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, i32>(out, high - low);
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_number_words() -> Fallible<()> {
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given the addition of the input and twenty-one henceforth the left
+        Given the product of the left and 1,000 henceforth the right
+        Given the sum of one, 2 and the third henceforth the total
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.ctx_mut().set_global::<_, i32>("input", 4);
+    instance.exec().unwrap();
+    let right = instance.ctx().get_global::<_, i32>("right").unwrap();
+    assert_eq!(right, Some(&25000));
+    let total = instance.ctx().get_global::<_, i32>("total").unwrap();
+    assert_eq!(total, Some(&6));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_number_before_and() -> Fallible<()> {
+    type Module<'a> = mod_type!(Between<'a>);
+    let mut ctx = ();
+    let script = Module::compile(
+        &mut ctx,
+        "Given the numbers between one hundred and two hundred henceforth the count",
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let count = instance.ctx().get_global::<_, i32>("count").unwrap();
+    assert_eq!(count, Some(&100));
+    Ok(())
+}
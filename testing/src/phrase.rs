@@ -0,0 +1,138 @@
+use crate::error::Fallible;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use ogma::matcher::{MatchError, Policy};
+use ogma::module::{ModuleList, WithPolicy};
+use ogma::object_query::Query;
+use ogma::phrase::PhraseScript;
+use ogma::vm::{Context, Trap};
+
+#[ogma_fn(Set, "Given the value d`value` henceforth q`out`")]
+fn set<'a>(ctx: &mut Context, value: i32, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, i32>(out, value);
+    Ok(())
+}
+
+#[ogma_fn(Add, "Add d`b` to q`input`")]
+fn add<'a>(ctx: &mut Context, b: i32, input: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let input = input.iter().next().unwrap().as_key().unwrap();
+    let a = ctx
+        .get_global::<_, i32>(input)?
+        .ok_or_else(|| Trap::MissingGlobal(input.to_string()))?;
+    ctx.set_global::<_, i32>(input, a + b);
+    Ok(())
+}
+
+type Module<'a> = mod_type!(Set<'a>, Add<'a>);
+
+fn module<'a>() -> ModuleList<'a, ()> {
+    mod_list!(() => Set, Add)
+}
+
+const SCRIPT: &str = r#"
+---
+title: Settlement
+---
+To open q`account` with d`amount`:
+    Given the value <amount> henceforth <account>
+
+To settle q`account`:
+    Add 2 to <account>
+    Add 3 to <account>
+
+Open the savings with 1
+Settle the savings
+Add 4 to the savings
+"#;
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_phrases() -> Fallible<()> {
+    let script = PhraseScript::parse(SCRIPT).unwrap();
+    for script in &[
+        script.compile(&module(), &mut ()).unwrap(),
+        script.compile_type::<Module, ()>(&mut ()).unwrap(),
+    ] {
+        assert_eq!(script.metadata().title, Some("Settlement"));
+        let mut instance = script.instance();
+        instance.exec().unwrap();
+        let savings = instance.ctx().get_global::<_, i32>("savings").unwrap();
+        assert_eq!(savings, Some(&10));
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_phrase_errors() -> Fallible<()> {
+    let src = SCRIPT.replace("Add 3 to", "Add plenty to");
+    let script = PhraseScript::parse(&src).unwrap();
+    let (line_num, err) = script.compile(&module(), &mut ()).err().unwrap();
+    assert_eq!(line_num, 12);
+    match err {
+        MatchError::InPhrase(phrase, line, err) => {
+            assert_eq!(phrase, "settle …");
+            assert_eq!(line, 9);
+            assert!(matches!(*err, MatchError::NoMatch(_)));
+        }
+        _ => panic!("expected an error in a phrase"),
+    }
+    let (line_num, err) =
+        PhraseScript::parse("To settle:\n  Add 2 to the savings\nSettle\n  | a |")
+            .err()
+            .unwrap();
+    assert_eq!(line_num, 2);
+    assert_eq!(err.to_string(), "unexpected doc string or data table");
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_phrase_bodies() -> Fallible<()> {
+    let src = r#"
+    To settle q`account`:
+        Add 2 to <account>
+
+        # the fee is charged separately
+        Add 3 to <account>
+    Given the value 1 henceforth the savings
+    Settle the savings
+    "#;
+    let script = PhraseScript::parse(src).unwrap();
+    assert_eq!(script.phrases[0].steps.len(), 2);
+    let script = script.compile(&module(), &mut ()).unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let savings = instance.ctx().get_global::<_, i32>("savings").unwrap();
+    assert_eq!(savings, Some(&6));
+    let (line_num, err) = PhraseScript::parse("Add 2 to <account>").err().unwrap();
+    assert_eq!(line_num, 0);
+    assert_eq!(
+        err.to_string(),
+        "invalid phrase: expected 'a value' at column 10, found '<account>'"
+    );
+    let (line_num, err) = PhraseScript::parse("To settle q`account`:\n  Add 2 to <acount>")
+        .err()
+        .unwrap();
+    assert_eq!(line_num, 1);
+    assert_eq!(
+        err.to_string(),
+        "invalid phrase: expected 'a variable of the phrase' at column 10, found '<acount>'"
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_phrase_policy() -> Fallible<()> {
+    let script = PhraseScript::parse("given THE value 2 henceforth the savings.").unwrap();
+    assert!(script.compile(&module(), &mut ()).is_err());
+    let lenient = WithPolicy::new(module(), Policy::lenient());
+    let script = script.compile(&lenient, &mut ()).unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let savings = instance.ctx().get_global::<_, i32>("savings").unwrap();
+    assert_eq!(savings, Some(&2));
+    Ok(())
+}
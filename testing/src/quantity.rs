@@ -0,0 +1,49 @@
+use crate::error::Fallible;
+use alloc::vec::Vec;
+use ogma::matcher::MatchError;
+use ogma::module::Module;
+use ogma::object_query::Query;
+use ogma::quantity::{Mass, Quantity, Unit};
+use ogma::vm::{Context, Trap};
+
+#[ogma_fn(Weigh, "Given a parcel of d`weight:Quantity<Mass>` henceforth q`out`")]
+fn weigh<'a>(ctx: &mut Context, weight: &Quantity<Mass>, out: &Vec<Query<'a>>) -> Result<(), Trap> {
+    let out = out.iter().next().unwrap().as_key().unwrap();
+    ctx.set_global::<_, f64>(out, weight.value_in(&Unit::GRAM).unwrap());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_quantity() -> Fallible<()> {
+    let mut ctx = ();
+    let module = mod_list!(() => Weigh);
+    let script = module
+        .compile(
+            &mut ctx,
+            "Given a parcel of 3 kilograms henceforth the output",
+        )
+        .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let out = instance.ctx().get_global::<_, f64>("output").unwrap();
+    assert_eq!(out, Some(&3000.0));
+    let (_, err) = module
+        .compile(&mut ctx, "Given a parcel of 250 ms henceforth the output")
+        .err()
+        .unwrap();
+    match err {
+        MatchError::NoMatch(ref candidates) => match candidates[0].error {
+            MatchError::IncompatibleUnit(ref mismatch) => {
+                assert_eq!(mismatch.column, 23);
+                assert_eq!(mismatch.found, "ms");
+            }
+            ref err => panic!("unexpected error {}", err),
+        },
+        _ => panic!("unexpected error {}", err),
+    }
+    assert!(err
+        .to_string()
+        .ends_with("incompatible unit 'ms' at column 23, expected a unit of mass"));
+    Ok(())
+}
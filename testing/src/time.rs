@@ -0,0 +1,122 @@
+use crate::error::Fallible;
+use alloc::string::{String, ToString};
+use ogma::bdd;
+use ogma::matcher::{Match, MatchError};
+use ogma::module::ModuleType;
+use ogma::time::chrono::NaiveDate;
+use ogma::time::{Date, Duration, FixedClock, TimedStep};
+use ogma::vm::{Context, Trap};
+
+#[ogma_fn(Due, "Given the task is due d`deadline:Date`")]
+fn due(ctx: &mut Context, deadline: Date) -> Result<(), Trap> {
+    ctx.set_global::<_, String>("deadline", deadline.to_string());
+    Ok(())
+}
+
+#[ogma_fn(Remind, "Given a reminder after d`delay:Duration`")]
+fn remind(ctx: &mut Context, delay: Duration) -> Result<(), Trap> {
+    ctx.set_global::<_, i64>("delay", delay.0.num_seconds());
+    Ok(())
+}
+
+#[given(Deadline, "the deadline is d`deadline:Date`")]
+fn deadline(ctx: &mut Context, deadline: Date) -> Result<(), Trap> {
+    ctx.set_global::<_, String>("deadline", deadline.to_string());
+    Ok(())
+}
+
+type Module = mod_type!(Due, Remind);
+
+fn clock() -> FixedClock {
+    FixedClock(
+        NaiveDate::from_ymd_opt(2026, 10, 14)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap(),
+    )
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_relative_date() -> Fallible<()> {
+    let mut ctx = clock();
+    let script = Module::compile(
+        &mut ctx,
+        r#"
+        Given the task is due tomorrow
+        Given a reminder after two hours and 30 minutes
+        "#,
+    )
+    .unwrap();
+    let mut instance = script.instance();
+    instance.exec().unwrap();
+    let deadline = instance.ctx().get_global::<_, String>("deadline").unwrap();
+    assert_eq!(deadline.map(String::as_str), Some("2026-10-15"));
+    let delay = instance.ctx().get_global::<_, i64>("delay").unwrap();
+    assert_eq!(delay, Some(&9000));
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_missing_clock() -> Fallible<()> {
+    let mut ctx = ();
+    assert!(Module::compile(&mut ctx, "Given the task is due March 3rd 2027").is_ok());
+    let (_, err) = Module::compile(&mut ctx, "Given the task is due next Friday")
+        .err()
+        .unwrap();
+    match err {
+        MatchError::NoMatch(ref candidates) => match candidates[0].error {
+            MatchError::InvalidVar(ref mismatch, ref err) => {
+                assert_eq!(mismatch.expected, "deadline");
+                assert!(matches!(**err, MatchError::MissingClock));
+            }
+            ref err => panic!("unexpected error {}", err),
+        },
+        _ => panic!("unexpected error {}", err),
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_bdd_date() -> Fallible<()> {
+    let mut ctx = bdd::Step::new();
+    let deadline = Deadline::match_str(&mut ctx, "Given the deadline is March 3rd 2027")?;
+    assert_eq!(
+        deadline.deadline.0,
+        NaiveDate::from_ymd_opt(2027, 3, 3).unwrap()
+    );
+    Ok(())
+}
+
+#[given(Holidays, "the holidays are l`days:Date`")]
+#[allow(clippy::ptr_arg)]
+fn holidays(ctx: &mut Context, days: &Vec<Date>) -> Result<(), Trap> {
+    ctx.set_global::<_, usize>("holidays", days.len());
+    Ok(())
+}
+
+#[cfg_attr(feature = "std", test)]
+#[cfg_attr(not(feature = "std"), test_case)]
+fn test_bdd_clock() -> Fallible<()> {
+    let mut ctx = TimedStep::new(clock());
+    let deadline = Deadline::match_str(&mut ctx, "Given the deadline is tomorrow")?;
+    assert_eq!(
+        deadline.deadline.0,
+        NaiveDate::from_ymd_opt(2026, 10, 15).unwrap()
+    );
+    assert!(matches!(ctx.step, bdd::Step::Given));
+    let holidays = Holidays::match_str(&mut ctx, "And the holidays are today and next Friday")?;
+    assert_eq!(
+        holidays.days,
+        [
+            Date(NaiveDate::from_ymd_opt(2026, 10, 14).unwrap()),
+            Date(NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()),
+        ]
+    );
+    let mut ctx = bdd::Step::new();
+    assert!(Deadline::match_str(&mut ctx, "Given the deadline is tomorrow").is_err());
+    assert!(matches!(ctx, bdd::Step::Start));
+    Ok(())
+}